@@ -2,12 +2,12 @@ use criterion::*;
 use queercat_lib::*;
 
 use std::io::{sink, Cursor, Seek, Write};
-const DATASET_A: &'static str = include_str!("bench-data/a");
-const DATASET_CYRILLIC: &'static str = include_str!("bench-data/cyrillic");
-const DATASET_ESCAPE: &'static str = include_str!("bench-data/escape");
-const DATASET_GLITCH: &'static str = include_str!("bench-data/glitch");
-const DATASET_QUEERCAT_SRC: &'static str = include_str!("bench-data/queercat");
-const DATASET_QUEERCAT_SRC_PP: &'static str = include_str!("bench-data/queercat-pp");
+const DATASET_A: &str = include_str!("bench-data/a");
+const DATASET_CYRILLIC: &str = include_str!("bench-data/cyrillic");
+const DATASET_ESCAPE: &str = include_str!("bench-data/escape");
+const DATASET_GLITCH: &str = include_str!("bench-data/glitch");
+const DATASET_QUEERCAT_SRC: &str = include_str!("bench-data/queercat");
+const DATASET_QUEERCAT_SRC_PP: &str = include_str!("bench-data/queercat-pp");
 
 const TEST_FLAGS: [Flag<'_>; 5] = [transgender(), lesbian(), gay(), aroace(), rainbow()];
 
@@ -16,8 +16,14 @@ fn create_queercat_24bit<'a>(flag: &Flag<'a>) -> QueerCat<'a, Box<dyn Write>, Bi
     QueerCat::new(colorizer, Box::new(black_box(sink())), flag.clone())
 }
 
+fn create_queercat_24bit_lut<'a>(flag: &Flag<'a>) -> QueerCat<'a, Box<dyn Write>, Bits24Lut> {
+    let gradient = color::Gradient::new(flag, color::DEFAULT_GRADIENT_RESOLUTION);
+    let colorizer = Bits24Lut::new(Bits24::new(QueerCatFrequency::Original), gradient);
+    QueerCat::new(colorizer, Box::new(black_box(sink())), flag.clone())
+}
+
 fn create_queercat_ansi<'a>(flag: &Flag<'a>) -> QueerCat<'a, Box<dyn Write>, Ansi> {
-    let colorizer = Ansi::new(flag.ansi_colors.len() as u32, QueerCatFrequency::Original);
+    let colorizer = Ansi::new(flag, QueerCatFrequency::Original);
     QueerCat::new(colorizer, Box::new(black_box(sink())), flag.clone())
 }
 
@@ -39,6 +45,24 @@ fn do_24bit(c: &mut Criterion, dataset_name: &'static str, dataset: &'static str
     group.finish()
 }
 
+fn do_24bit_lut(c: &mut Criterion, dataset_name: &'static str, dataset: &'static str) {
+    let mut group = c.benchmark_group(dataset_name);
+    let mut data = Cursor::new(dataset.as_bytes());
+    group.throughput(Throughput::Bytes(dataset.len() as u64));
+
+    for flag in TEST_FLAGS {
+        let mut cat = create_queercat_24bit_lut(&flag);
+        group.bench_function(BenchmarkId::new(flag.name, "24 bit lut"), |b| {
+            b.iter(|| {
+                cat.cat(black_box(&mut data)).unwrap();
+                data.rewind()
+            })
+        });
+        data.rewind().unwrap()
+    }
+    group.finish()
+}
+
 fn do_ansi(c: &mut Criterion, dataset_name: &'static str, dataset: &'static str) {
     let mut group = c.benchmark_group(dataset_name);
     let mut data = Cursor::new(dataset.as_bytes());
@@ -57,6 +81,32 @@ fn do_ansi(c: &mut Criterion, dataset_name: &'static str, dataset: &'static str)
     group.finish()
 }
 
+#[cfg(feature = "rayon")]
+fn do_par_24bit(c: &mut Criterion, dataset_name: &'static str, dataset: &'static str) {
+    let mut group = c.benchmark_group(dataset_name);
+    let mut data = Cursor::new(dataset.as_bytes());
+    group.throughput(Throughput::Bytes(dataset.len() as u64));
+
+    for flag in TEST_FLAGS {
+        for (granularity, name) in [
+            (Granularity::Grapheme, "24 bit parallel"),
+            (Granularity::Word, "24 bit parallel words"),
+        ] {
+            let colorizer = Bits24::new(QueerCatFrequency::Original);
+            let mut cat = ParQueerCat::new(colorizer, black_box(sink()), flag.clone())
+                .with_granularity(granularity);
+            group.bench_function(BenchmarkId::new(flag.name, name), |b| {
+                b.iter(|| {
+                    cat.cat(black_box(&mut data)).unwrap();
+                    data.rewind()
+                })
+            });
+            data.rewind().unwrap()
+        }
+    }
+    group.finish()
+}
+
 fn bench(c: &mut Criterion) {
     for (dataset_name, dataset) in [
         ("glitch", DATASET_GLITCH),
@@ -68,6 +118,9 @@ fn bench(c: &mut Criterion) {
     ] {
         do_ansi(c, dataset_name, dataset);
         do_24bit(c, dataset_name, dataset);
+        do_24bit_lut(c, dataset_name, dataset);
+        #[cfg(feature = "rayon")]
+        do_par_24bit(c, dataset_name, dataset);
     }
 }
 
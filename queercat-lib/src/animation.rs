@@ -0,0 +1,206 @@
+//! Animating text like `lolcat -a`. Every line is drawn over and over with the gradient moving a bit further each
+//! frame before going on to the next line.
+//!
+//! The cursor is hidden while animating and shown again at the end, even when writing fails. If the animation is
+//! interrupted another way, like with SIGINT, write [`RESTORE_TERMINAL`] before exiting.
+//!
+//! # Examples
+//! ```
+//! use queercat_lib::{transgender, Animation, Bits24, Moving, QueerCatFrequency};
+//! let colorizer = Bits24::new(QueerCatFrequency::Original);
+//! let mut animation = Animation::new(transgender(), |offset| colorizer.clone().with_offset(offset))
+//!     .with_duration(3)
+//!     .with_speed(1000.0);
+//! let mut output = Vec::new();
+//! animation.animate(&b"trans rights\nare human rights\n"[..], &mut output)?;
+//! assert!(output.ends_with(b"\x1b[0m\x1b[?25h"));
+//! # Ok::<(), std::io::Error>(())
+//! ```
+
+use crate::colorizer::{Colorizer, Position};
+use crate::driver::{EscapeMode, Granularity, InvalidUtf8, Painter};
+use crate::flag::Flag;
+use std::io::{self, BufRead, Write};
+use std::marker::PhantomData;
+use std::time::Duration;
+
+/// The default number of frames that every line is drawn for
+pub const DEFAULT_DURATION: u32 = 12;
+/// The default number of frames per second
+pub const DEFAULT_SPEED: f32 = 20.0;
+/// The default distance that the gradient moves every frame, in flags
+pub const DEFAULT_STEP: f32 = 1.0 / 12.0;
+
+/// Resets the colors and shows the cursor
+pub const RESTORE_TERMINAL: &[u8] = b"\x1b[0m\x1b[?25h";
+const HIDE_CURSOR: &[u8] = b"\x1b[?25l";
+const SAVE_CURSOR: &[u8] = b"\x1b7";
+const RESTORE_CURSOR: &[u8] = b"\x1b8";
+
+/// Draws text line by line with a moving gradient
+pub struct Animation<'a, C, F> {
+    /// Makes a colorizer with the gradient moved by an offset
+    make_colorizer: F,
+    flag: Flag<'a>,
+    escape_mode: EscapeMode,
+    invalid_utf8: InvalidUtf8,
+    granularity: Granularity,
+    duration: u32,
+    frame_time: Duration,
+    step: f32,
+    colorizer: PhantomData<fn() -> C>,
+}
+
+impl<'a, C: Colorizer, F: FnMut(f32) -> C> Animation<'a, C, F> {
+    /// `make_colorizer` makes a colorizer with the gradient moved by the offset that it is called with, usually with
+    /// `with_offset`.
+    #[must_use]
+    pub const fn new(flag: Flag<'a>, make_colorizer: F) -> Self {
+        Self {
+            make_colorizer,
+            flag,
+            escape_mode: EscapeMode::Strip,
+            invalid_utf8: InvalidUtf8::Replace,
+            granularity: Granularity::Grapheme,
+            duration: DEFAULT_DURATION,
+            frame_time: Duration::from_millis(50),
+            step: DEFAULT_STEP,
+            colorizer: PhantomData,
+        }
+    }
+
+    /// Sets how escape sequences in the input are handled. Defaults to [`EscapeMode::Strip`]
+    #[must_use]
+    pub const fn with_escape_mode(mut self, escape_mode: EscapeMode) -> Self {
+        self.escape_mode = escape_mode;
+        self
+    }
+
+    /// Sets what happens to input that is not valid utf-8. Defaults to [`InvalidUtf8::Replace`]
+    #[must_use]
+    pub const fn with_invalid_utf8(mut self, invalid_utf8: InvalidUtf8) -> Self {
+        self.invalid_utf8 = invalid_utf8;
+        self
+    }
+
+    /// Sets how much of the text gets the same color. Defaults to [`Granularity::Grapheme`]
+    #[must_use]
+    pub const fn with_granularity(mut self, granularity: Granularity) -> Self {
+        self.granularity = granularity;
+        self
+    }
+
+    /// Sets the number of frames that every line is drawn for. A duration of 0 is treated as 1.
+    /// Defaults to [`DEFAULT_DURATION`]
+    #[must_use]
+    pub fn with_duration(mut self, duration: u32) -> Self {
+        self.duration = duration.max(1);
+        self
+    }
+
+    /// Sets the number of frames per second. Speeds that are not positive don't wait between frames at all.
+    /// Defaults to [`DEFAULT_SPEED`]
+    #[must_use]
+    pub fn with_speed(mut self, speed: f32) -> Self {
+        self.frame_time = Duration::try_from_secs_f32(speed.recip()).unwrap_or(Duration::ZERO);
+        self
+    }
+
+    /// Sets how far the gradient moves every frame, in flags. Defaults to [`DEFAULT_STEP`]
+    #[must_use]
+    pub const fn with_step(mut self, step: f32) -> Self {
+        self.step = step;
+        self
+    }
+
+    /// Colorizes `line` as the line at `line_number` with the gradient moved by `offset`, and resets the colors after it.
+    /// `line` shouldn't contain line breaks.
+    /// # Errors
+    /// Returns `Err` when writing to `writer` fails or `line` is not valid utf-8 with [`InvalidUtf8::Error`]
+    pub fn render_line<W: Write>(
+        &mut self,
+        line: &[u8],
+        line_number: u32,
+        offset: f32,
+        writer: &mut W,
+    ) -> Result<(), io::Error> {
+        let mut colorizer = (self.make_colorizer)(offset);
+        colorizer.seek(Position {
+            line: line_number,
+            col: 0,
+        });
+        let mut painter = Painter::new(colorizer, self.flag.clone());
+        painter.escape_mode = self.escape_mode;
+        painter.invalid_utf8 = self.invalid_utf8;
+        painter.set_granularity(self.granularity);
+        painter.write(line, writer)?;
+        painter.finish(writer)?;
+        painter.write_reset(writer)
+    }
+
+    /// Draws every frame of `line`, the line at `line_number`, on top of each other, and leaves the cursor after it.
+    /// Every frame is written at once, and `writer` is flushed after it.
+    /// # Errors
+    /// Returns `Err` when writing to `writer` fails or `line` is not valid utf-8 with [`InvalidUtf8::Error`]
+    pub fn animate_line<W: Write>(
+        &mut self,
+        line: &[u8],
+        line_number: u32,
+        writer: &mut W,
+    ) -> Result<(), io::Error> {
+        if line.is_empty() {
+            return Ok(());
+        }
+        let mut frame = Vec::new();
+        for i in 0..self.duration {
+            frame.clear();
+            frame.extend_from_slice(if i == 0 { SAVE_CURSOR } else { RESTORE_CURSOR });
+            #[allow(clippy::cast_precision_loss)]
+            let offset = self.step * i as f32;
+            self.render_line(line, line_number, offset, &mut frame)?;
+            writer.write_all(&frame)?;
+            writer.flush()?;
+            std::thread::sleep(self.frame_time);
+        }
+        Ok(())
+    }
+
+    fn animate_impl<R: BufRead, W: Write>(
+        &mut self,
+        mut input: R,
+        writer: &mut W,
+    ) -> Result<(), io::Error> {
+        writer.write_all(HIDE_CURSOR)?;
+        let mut line = Vec::new();
+        let mut line_number = 0u32;
+        loop {
+            line.clear();
+            if input.read_until(b'\n', &mut line)? == 0 {
+                return Ok(());
+            }
+            let line_break = line.ends_with(b"\n");
+            if line_break {
+                line.pop();
+            }
+            self.animate_line(&line, line_number, writer)?;
+            if line_break {
+                writer.write_all(b"\n")?;
+            }
+            line_number = line_number.wrapping_add(1);
+        }
+    }
+
+    /// Animates every line of `input` and writes it to `writer`
+    /// # Errors
+    /// Returns `Err` when writing to `writer` or reading `input` fails
+    pub fn animate<R: BufRead, W: Write>(
+        &mut self,
+        input: R,
+        mut writer: W,
+    ) -> Result<(), io::Error> {
+        let res = self.animate_impl(input, &mut writer);
+        writer.write_all(RESTORE_TERMINAL)?;
+        writer.flush()?;
+        res
+    }
+}
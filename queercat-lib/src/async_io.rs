@@ -0,0 +1,179 @@
+//! Async colorization with tokio. Requires the `tokio` feature.
+//!
+//! These use the same state machine as [`QueerCat`](crate::QueerCat) and [`ColorWriter`](crate::ColorWriter), so
+//! the output is exactly the same as the output of the blocking versions.
+//!
+//! # Examples
+//! ```
+//! use queercat_lib::{transgender, AsyncQueerCat, Bits24, QueerCatFrequency};
+//! # let runtime = tokio::runtime::Builder::new_current_thread().build()?;
+//! # runtime.block_on(async {
+//! let colorizer = Bits24::new(QueerCatFrequency::Original);
+//! let mut cat = AsyncQueerCat::new(colorizer, Vec::new(), transgender());
+//! cat.cat(&b"trans rights are human rights\n"[..]).await?;
+//! assert!(cat.into_inner().starts_with(b"\x1b[38;2;"));
+//! # Ok::<(), std::io::Error>(())
+//! # })?;
+//! # Ok::<(), std::io::Error>(())
+//! ```
+
+use crate::colorizer::Colorizer;
+use crate::driver::Painter;
+use crate::flag::Flag;
+use std::io;
+use std::pin::Pin;
+use std::task::{ready, Context, Poll};
+use tokio::io::{AsyncBufRead, AsyncBufReadExt, AsyncWrite, AsyncWriteExt};
+
+/// The async version of [`QueerCat`](crate::QueerCat)
+pub struct AsyncQueerCat<'a, W: AsyncWrite + Unpin, C: Colorizer> {
+    painter: Painter<'a, C>,
+    writer: W,
+    /// Colorized output that is waiting to be written
+    buf: Vec<u8>,
+}
+
+impl<'a, W: AsyncWrite + Unpin, C: Colorizer> AsyncQueerCat<'a, W, C> {
+    #[must_use]
+    pub const fn new(colorizer: C, writer: W, flag: Flag<'a>) -> Self {
+        Self {
+            painter: Painter::new(colorizer, flag),
+            writer,
+            buf: Vec::new(),
+        }
+    }
+
+    crate::driver::painter_builders! {
+        /// Sets whether the colors of the terminal are reset after each call to [`AsyncQueerCat::cat`]. Defaults to `true`.
+    }
+
+    /// Returns the inner writer
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+
+    async fn write_buf(&mut self) -> Result<(), io::Error> {
+        self.writer.write_all(&self.buf).await?;
+        self.buf.clear();
+        Ok(())
+    }
+
+    async fn cat_impl<R: AsyncBufRead + Unpin>(&mut self, mut file: R) -> Result<(), io::Error> {
+        loop {
+            let buf = file.fill_buf().await?;
+            let buf_len = buf.len();
+            if buf.is_empty() {
+                break;
+            }
+            self.painter.write(buf, &mut self.buf)?;
+            file.consume(buf_len);
+            self.write_buf().await?;
+        }
+        self.painter.finish(&mut self.buf)
+    }
+
+    /// Colorizes input from `file` and writes it to the `writer`.
+    /// # Errors
+    /// Returns `Err` when writing with `self.writer` or reading `file` fails
+    pub async fn cat<R: AsyncBufRead + Unpin>(&mut self, file: R) -> Result<(), io::Error> {
+        let res = self.cat_impl(file).await;
+        self.painter.write_reset(&mut self.buf)?;
+        self.write_buf().await?;
+        res
+    }
+}
+
+/// The async version of [`ColorWriter`](crate::ColorWriter). Colorizes everything that is written to it before
+/// passing it on to the inner writer.
+///
+/// Flushing writes what is kept for the next write and resets the colors of the terminal. Shutting it down ends the
+/// input, like [`ColorWriter::into_inner`](crate::ColorWriter::into_inner).
+pub struct AsyncColorWriter<'a, W: AsyncWrite + Unpin, C: Colorizer> {
+    painter: Painter<'a, C>,
+    writer: W,
+    /// Colorized output that is waiting to be written
+    buf: Vec<u8>,
+}
+
+impl<'a, W: AsyncWrite + Unpin, C: Colorizer> AsyncColorWriter<'a, W, C> {
+    #[must_use]
+    pub const fn new(colorizer: C, writer: W, flag: Flag<'a>) -> Self {
+        Self {
+            painter: Painter::new(colorizer, flag),
+            writer,
+            buf: Vec::new(),
+        }
+    }
+
+    crate::driver::painter_builders! {
+        /// Sets whether the colors of the terminal are reset after flushing. Defaults to `true`.
+    }
+
+    /// A reference to the inner writer
+    pub const fn get_ref(&self) -> &W {
+        &self.writer
+    }
+
+    /// A mutable reference to the inner writer. Writing to it directly can mess up the colors.
+    pub fn get_mut(&mut self) -> &mut W {
+        &mut self.writer
+    }
+
+    /// Returns the inner writer. Output that has not been written yet is lost, so shut down the writer first.
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+
+    /// Writes all of the colorized output that is waiting
+    fn poll_write_buf(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), io::Error>> {
+        let mut written = 0;
+        let res = loop {
+            if written == self.buf.len() {
+                break Poll::Ready(Ok(()));
+            }
+            match Pin::new(&mut self.writer).poll_write(cx, &self.buf[written..]) {
+                Poll::Ready(Ok(0)) => break Poll::Ready(Err(io::ErrorKind::WriteZero.into())),
+                Poll::Ready(Ok(n)) => written += n,
+                Poll::Ready(Err(e)) => break Poll::Ready(Err(e)),
+                Poll::Pending => break Poll::Pending,
+            }
+        };
+        self.buf.drain(..written);
+        res
+    }
+}
+
+impl<W: AsyncWrite + Unpin, C: Colorizer> AsyncWrite for AsyncColorWriter<'_, W, C>
+where
+    Self: Unpin,
+{
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<Result<usize, io::Error>> {
+        let this = self.get_mut();
+        ready!(this.poll_write_buf(cx))?;
+        this.painter.write(buf, &mut this.buf)?;
+        // the input is accepted, so the output can be written later if the writer isn't ready
+        if let Poll::Ready(Err(e)) = this.poll_write_buf(cx) {
+            return Poll::Ready(Err(e));
+        }
+        Poll::Ready(Ok(buf.len()))
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), io::Error>> {
+        let this = self.get_mut();
+        this.painter.flush(&mut this.buf)?;
+        ready!(this.poll_write_buf(cx))?;
+        Pin::new(&mut this.writer).poll_flush(cx)
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), io::Error>> {
+        let this = self.get_mut();
+        this.painter.finish(&mut this.buf)?;
+        this.painter.flush(&mut this.buf)?;
+        ready!(this.poll_write_buf(cx))?;
+        Pin::new(&mut this.writer).poll_shutdown(cx)
+    }
+}
@@ -2,6 +2,8 @@
 //! The module for working with colors. This should generally not be be accessed unless you know what you're doing.
 use crate::flag::Flag;
 use crate::{ColorV, Extended};
+use std::io::Write;
+use std::sync::OnceLock;
 
 /// An rgb color. Used in 24-bit mode
 #[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
@@ -83,6 +85,55 @@ impl Color {
         }
     }
 
+    /// The 24 bit color of `flag` at `theta`
+    #[must_use]
+    pub fn from_flag(theta: ColorV, flag: &Flag<'_>) -> Self {
+        if flag.is_rainbow() {
+            return Self::rainbow(theta);
+        }
+        // a whole flag doesn't fit into a `ColorV`, and a single stripe has nothing to blend with anyway
+        if let [color] = flag.stripe_colors {
+            return *color;
+        }
+        let stripe_size = Extended::from_num(flag.stripe_colors.len()).recip();
+        let stripe_size = ColorV::wrapping_from_num(stripe_size);
+        Self::stripe(theta, flag, stripe_size)
+    }
+
+    /// The 24 bit color at `position` on `flag` when it is laid out as its stripes from first to last, like the
+    /// actual flag. With `blend`, stripes blend into their neighbors near their edges, where higher `flag.factor`s
+    /// give sharper edges.
+    #[must_use]
+    #[allow(
+        clippy::cast_precision_loss,
+        clippy::cast_possible_truncation,
+        clippy::cast_sign_loss
+    )]
+    pub fn from_stripes(position: ColorV, flag: &Flag<'_>, blend: bool) -> Self {
+        if flag.is_rainbow() {
+            return Self::rainbow(position);
+        }
+        let colors = flag.stripe_colors;
+        let scaled = position.to_num::<f64>() * colors.len() as f64;
+        let i = (scaled as usize).min(colors.len() - 1);
+        if !blend {
+            return colors[i];
+        }
+        // -1 at the start of the stripe, 0 in the middle and 1 at the end
+        let from_middle = (scaled - i as f64) * 2.0 - 1.0;
+        let neighbor = if from_middle < 0.0 {
+            i.checked_sub(1)
+        } else {
+            Some(i + 1).filter(|&next| next < colors.len())
+        };
+        let Some(neighbor) = neighbor else {
+            return colors[i];
+        };
+        // the edges are halfway between the stripes
+        let weight = from_middle.abs().powf(flag.factor.to_num()) / 2.0;
+        colors[i].lerp(colors[neighbor], ColorV::saturating_from_num(1.0 - weight))
+    }
+
     #[must_use]
     pub fn stripe(theta: ColorV, flag: &Flag<'_>, stripe_size: ColorV) -> Self {
         let theta = Extended::from_num(theta);
@@ -103,7 +154,40 @@ impl Color {
     #[must_use]
     pub fn mix(self, other: Color, balance: ColorV, factor: Extended) -> Color {
         let balance = ColorV::from_num(balance.to_num::<f32>().powf(factor.to_num::<f32>()));
+        self.lerp(other, balance)
+    }
 
+    /// How different `self` and `other` look to people, with 0 for the same color.
+    /// Colors are compared in the Oklab color space, so a distance of 0.1 is about as noticeable for every color.
+    pub fn distance(self, other: Color) -> f32 {
+        let [l1, a1, b1] = self.oklab();
+        let [l2, a2, b2] = other.oklab();
+        ((l1 - l2).powi(2) + (a1 - a2).powi(2) + (b1 - b2).powi(2)).sqrt()
+    }
+
+    /// The color as `[l, a, b]` in the Oklab color space
+    #[allow(clippy::many_single_char_names)]
+    fn oklab(self) -> [f32; 3] {
+        fn linear(v: ColorV) -> f32 {
+            let v = v.to_num::<f32>();
+            if v <= 0.04045 {
+                v / 12.92
+            } else {
+                ((v + 0.055) / 1.055).powf(2.4)
+            }
+        }
+        let (r, g, b) = (linear(self.red), linear(self.green), linear(self.blue));
+        let l = (0.412_221_46 * r + 0.536_332_55 * g + 0.051_445_995 * b).cbrt();
+        let m = (0.211_903_5 * r + 0.680_699_5 * g + 0.107_396_96 * b).cbrt();
+        let s = (0.088_302_46 * r + 0.281_718_85 * g + 0.629_978_7 * b).cbrt();
+        [
+            0.210_454_26 * l + 0.793_617_8 * m - 0.004_072_047 * s,
+            1.977_998_5 * l - 2.428_592_2 * m + 0.450_593_7 * s,
+            0.025_904_037 * l + 0.782_771_77 * m - 0.808_675_77 * s,
+        ]
+    }
+
+    fn lerp(self, other: Color, balance: ColorV) -> Color {
         let red = mix_field(self.red(), other.red(), balance);
         let green = mix_field(self.green(), other.green(), balance);
         let blue = mix_field(self.blue(), other.blue(), balance);
@@ -140,13 +224,201 @@ impl std::fmt::Display for Color {
     }
 }
 
+/// The theta in the middle of the `i`th of `len` equally long parts of a flag
+pub(crate) fn middle(i: usize, len: usize) -> ColorV {
+    #[allow(clippy::cast_possible_truncation)]
+    ColorV::from_bits((((2 * i as u64 + 1) << 31) / len as u64) as u32)
+}
+
+/// The default number of samples in a [`Gradient`]
+pub const DEFAULT_GRADIENT_RESOLUTION: usize = 4096;
+
+/// The 24 bit colors of a flag sampled at evenly spaced thetas, along with their escapes.
+/// Looking up a color is just an index, so it is much faster than calculating it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Gradient {
+    colors: Vec<Color>,
+    /// The escapes of all of the colors, one after another
+    escapes: Vec<u8>,
+    /// The end of every escape in `escapes`
+    ends: Vec<usize>,
+}
+
+impl Gradient {
+    /// Samples the colors of `flag` `resolution` times. A resolution of 0 is treated as 1.
+    #[must_use]
+    pub fn new(flag: &Flag<'_>, resolution: usize) -> Self {
+        Self::from_fn(resolution, |theta| Color::from_flag(theta, flag))
+    }
+
+    /// Samples `color` at `resolution` evenly spaced thetas, like [`Gradient::new`] does with the colors of a flag.
+    #[must_use]
+    pub fn from_fn(resolution: usize, mut color: impl FnMut(ColorV) -> Color) -> Self {
+        let resolution = resolution.max(1);
+        let mut colors = Vec::with_capacity(resolution);
+        let mut escapes = Vec::new();
+        let mut ends = Vec::with_capacity(resolution);
+        for i in 0..resolution {
+            // sample the middle of the range of thetas that map to `i`
+            let color = color(middle(i, resolution));
+            colors.push(color);
+            // writing to a `Vec` can't fail
+            let _ = write!(escapes, "{color}");
+            ends.push(escapes.len());
+        }
+        Self {
+            colors,
+            escapes,
+            ends,
+        }
+    }
+
+    /// The number of samples
+    pub fn resolution(&self) -> usize {
+        self.colors.len()
+    }
+
+    /// The index of the sample that is used for `theta`
+    pub fn index(&self, theta: ColorV) -> usize {
+        let scaled = u64::from(theta.to_bits()) * self.colors.len() as u64;
+        #[allow(clippy::cast_possible_truncation)]
+        let index = (scaled >> 32) as usize;
+        index
+    }
+
+    /// The sample at `index`
+    pub fn color(&self, index: usize) -> Color {
+        self.colors[index]
+    }
+
+    /// The escape that sets the color of the sample at `index`
+    pub fn escape(&self, index: usize) -> &[u8] {
+        let start = index.checked_sub(1).map_or(0, |prev| self.ends[prev]);
+        &self.escapes[start..self.ends[index]]
+    }
+}
+
+/// A color from a [`Gradient`], which knows the index of its escape. Colors are equal when they look the same, so
+/// neighboring samples with the same color don't write their escape again.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct GradientColor {
+    pub index: usize,
+    pub color: Color,
+}
+
+impl PartialEq for GradientColor {
+    fn eq(&self, other: &Self) -> bool {
+        self.color == other.color
+    }
+}
+
+impl Eq for GradientColor {}
+
+impl std::fmt::Display for GradientColor {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.color.fmt(f)
+    }
+}
+
 /// An color that uses ansi color codes
 #[allow(clippy::module_name_repetitions)]
-#[derive(Clone, Copy, PartialEq, Default, Eq)]
+#[derive(Clone, Copy, Debug, PartialEq, Default, Eq)]
 pub struct AnsiColor(pub u8);
 
+impl AnsiColor {
+    /// The color that xterm shows for the code by default
+    pub fn color(self) -> Color {
+        // the levels of red, green and blue in the 6x6x6 color cube
+        let level = |v: u8| u32::from(if v == 0 { 0 } else { 55 + 40 * v });
+        match self.0 {
+            0..=15 => BASIC_PALETTE[usize::from(self.0)],
+            16..=231 => {
+                let i = self.0 - 16;
+                Color::from_hex(level(i / 36) << 16 | level(i / 6 % 6) << 8 | level(i % 6))
+            }
+            _ => {
+                let gray = u32::from(8 + 10 * (self.0 - 232));
+                Color::from_hex(gray << 16 | gray << 8 | gray)
+            }
+        }
+    }
+
+    /// The color from the color cube and the grays that looks the most like `color`. The 16 basic colors are never
+    /// picked, since they look different in every terminal.
+    #[allow(clippy::cast_possible_truncation)]
+    pub fn nearest(color: Color) -> Self {
+        static PALETTE: OnceLock<Vec<[f32; 3]>> = OnceLock::new();
+        let palette =
+            PALETTE.get_or_init(|| (16..=255).map(|code| Self(code).color().oklab()).collect());
+        Self(16 + nearest(palette, color) as u8)
+    }
+}
+
 impl std::fmt::Display for AnsiColor {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(f, "\x1b[38;5;{}m", self.0)
     }
 }
+
+/// The 16 basic ansi colors as xterm shows them by default. Most terminals use similar colors, but users can change
+/// them.
+pub const BASIC_PALETTE: [Color; 16] = [
+    Color::from_hex(0x00_00_00),
+    Color::from_hex(0xcd_00_00),
+    Color::from_hex(0x00_cd_00),
+    Color::from_hex(0xcd_cd_00),
+    Color::from_hex(0x00_00_ee),
+    Color::from_hex(0xcd_00_cd),
+    Color::from_hex(0x00_cd_cd),
+    Color::from_hex(0xe5_e5_e5),
+    Color::from_hex(0x7f_7f_7f),
+    Color::from_hex(0xff_00_00),
+    Color::from_hex(0x00_ff_00),
+    Color::from_hex(0xff_ff_00),
+    Color::from_hex(0x5c_5c_ff),
+    Color::from_hex(0xff_00_ff),
+    Color::from_hex(0x00_ff_ff),
+    Color::from_hex(0xff_ff_ff),
+];
+
+/// One of the 16 basic ansi colors, which even the Linux console and most serial terminals support.
+/// 0 to 7 are the normal colors and 8 to 15 are their bright versions, and higher numbers wrap around.
+#[derive(Clone, Copy, Debug, PartialEq, Default, Eq)]
+pub struct BasicColor(pub u8);
+
+impl BasicColor {
+    /// The color that xterm shows for the code by default
+    pub fn color(self) -> Color {
+        BASIC_PALETTE[usize::from(self.0 & 15)]
+    }
+
+    /// The basic color in [`BASIC_PALETTE`] that looks the most like `color`
+    #[allow(clippy::cast_possible_truncation)]
+    pub fn nearest(color: Color) -> Self {
+        static PALETTE: OnceLock<[[f32; 3]; 16]> = OnceLock::new();
+        let palette = PALETTE.get_or_init(|| BASIC_PALETTE.map(Color::oklab));
+        Self(nearest(palette, color) as u8)
+    }
+}
+
+/// The index of the color in `palette` that looks the most like `color`. The palette is in the Oklab color space.
+fn nearest(palette: &[[f32; 3]], color: Color) -> usize {
+    let [l, a, b] = color.oklab();
+    let distance = |[l2, a2, b2]: &[f32; 3]| (l - l2).powi(2) + (a - a2).powi(2) + (b - b2).powi(2);
+    palette
+        .iter()
+        .enumerate()
+        .min_by(|(_, x), (_, y)| distance(x).total_cmp(&distance(y)))
+        .map_or(0, |(i, _)| i)
+}
+
+impl std::fmt::Display for BasicColor {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let index = self.0 & 15;
+        if index < 8 {
+            write!(f, "\x1b[{}m", 30 + index)
+        } else {
+            write!(f, "\x1b[{}m", 90 + index - 8)
+        }
+    }
+}
@@ -1,8 +1,13 @@
 #![allow(clippy::must_use_candidate)]
 
-use crate::color::{AnsiColor, Color};
-use crate::flag::Flag;
-use crate::{ColorV, Extended};
+use crate::color::{middle, AnsiColor, BasicColor, Color, Gradient, GradientColor};
+use crate::dither;
+use crate::flag::{Flag, RAINBOW_ANSI_COLORS};
+use crate::geometry::Geometry;
+use crate::{ColorV, SignedExtended};
+use std::io::{self, Write};
+use std::sync::Arc;
+use unicode_width::UnicodeWidthStr;
 
 #[derive(Debug, Clone, Copy)]
 pub enum QueerCatFrequency {
@@ -17,13 +22,13 @@ pub enum QueerCatFrequency {
 }
 
 impl QueerCatFrequency {
-    /// Converts `self` to a tuple of `(horizontal, vertical)`
-    pub fn as_freq(self) -> (Extended, Extended) {
+    /// Converts `self` to a tuple of `(vertical, horizontal)`
+    fn as_f32(self) -> (f32, f32) {
         use QueerCatFrequency::{
             Aaaaaaaaaaaaa, Custom, Fast, Horizontal, HyperGay, Original, UltraHyperGay, Vertical,
         };
 
-        let (freq_v, freq_h) = match self {
+        match self {
             Original => (0.23, 0.1),
             Fast => (0.4, 0.4),
             HyperGay => (0.9, 0.9),
@@ -32,9 +37,273 @@ impl QueerCatFrequency {
             Vertical(v) => (v, 0.0),
             Horizontal(h) => (0.0, h),
             Custom(v, x) => (v, x),
+        }
+    }
+
+    /// Converts `self` to a tuple of `(horizontal, vertical)`. Negative frequencies go the other way.
+    /// Frequencies that don't fit into a [`SignedExtended`], including infinities, are saturated to its largest or
+    /// smallest value, and NaN is 0.
+    pub fn as_freq(self) -> (SignedExtended, SignedExtended) {
+        let (freq_v, freq_h) = self.as_f32();
+        let convert = |freq: f32| {
+            if freq.is_nan() {
+                SignedExtended::ZERO
+            } else {
+                SignedExtended::saturating_from_num(freq)
+            }
         };
+        (convert(freq_h), convert(freq_v))
+    }
+
+    /// The same frequency going the other way, so that the flag goes from right to left and bottom to top
+    #[must_use]
+    pub fn reversed(self) -> Self {
+        let (freq_v, freq_h) = self.as_f32();
+        QueerCatFrequency::Custom(-freq_v, -freq_h)
+    }
+}
+
+/// How many columns a grapheme moves the cursor
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ColumnMode {
+    /// Every grapheme takes up one column
+    Graphemes,
+    /// Graphemes take up as many columns as terminal cells. Wide characters like CJK ideographs and emoji take up
+    /// two columns, and zero-width characters like lone combining marks take up none.
+    #[default]
+    DisplayWidth,
+}
+
+impl ColumnMode {
+    /// The number of columns that `grapheme` takes up
+    pub fn width(self, grapheme: &str) -> u32 {
+        match self {
+            ColumnMode::Graphemes => 1,
+            // ascii is always one column wide, so skip the table lookup
+            ColumnMode::DisplayWidth if grapheme.len() == 1 => 1,
+            #[allow(clippy::cast_possible_truncation)]
+            ColumnMode::DisplayWidth => grapheme.width() as u32,
+        }
+    }
+}
+
+/// The default distance between tab stops
+pub const DEFAULT_TAB_WIDTH: u32 = 8;
+
+/// A line and column in the terminal, both starting at 0
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Position {
+    pub line: u32,
+    pub col: u32,
+}
+
+/// The offset between 0 and 1 that `seed` picks. It is the same on every platform.
+fn seed_offset(seed: u64) -> ColorV {
+    // splitmix64, so that seeds that are close together still pick very different offsets
+    let mut z = seed.wrapping_add(0x9e37_79b9_7f4a_7c15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^= z >> 31;
+    #[allow(clippy::cast_possible_truncation)]
+    ColorV::from_bits((z >> 32) as u32)
+}
+
+/// Tracks the line and column that graphemes end up at in the terminal
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Cursor {
+    line: u32,
+    col: u32,
+    column_mode: ColumnMode,
+    tab_width: u32,
+}
+
+impl Default for Cursor {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Cursor {
+    /// Creates a cursor at the start of the first line
+    pub const fn new() -> Self {
+        Self {
+            line: 0,
+            col: 0,
+            column_mode: ColumnMode::DisplayWidth,
+            tab_width: DEFAULT_TAB_WIDTH,
+        }
+    }
+    /// Sets how many columns each grapheme takes up
+    #[must_use]
+    pub const fn with_column_mode(mut self, column_mode: ColumnMode) -> Self {
+        self.column_mode = column_mode;
+        self
+    }
+    /// Sets the distance between tab stops. A width of 0 is treated as 1.
+    #[must_use]
+    pub fn with_tab_width(mut self, tab_width: u32) -> Self {
+        self.tab_width = tab_width.max(1);
+        self
+    }
+    /// The current line
+    pub const fn line(&self) -> u32 {
+        self.line
+    }
+    /// The current column
+    pub const fn col(&self) -> u32 {
+        self.col
+    }
+    /// The current line and column
+    pub const fn position(&self) -> Position {
+        Position {
+            line: self.line,
+            col: self.col,
+        }
+    }
+    /// Moves the cursor to `position`
+    pub fn seek(&mut self, position: Position) {
+        self.line = position.line;
+        self.col = position.col;
+    }
+
+    /// Moves the cursor like a terminal does when printing `grapheme`. Lines and columns wrap around to 0 after
+    /// `u32::MAX` instead of overflowing.
+    pub fn advance(&mut self, grapheme: &str) {
+        match grapheme.as_bytes() {
+            // printable ascii is by far the most common, so check it first
+            [0x20..=0x7e] => self.col = self.col.wrapping_add(1),
+            // `\r\n` is a single grapheme
+            b"\n" | b"\r\n" => {
+                self.line = self.line.wrapping_add(1);
+                self.col = 0;
+            }
+            b"\r" => self.col = 0,
+            b"\t" => {
+                let stops = self.col / self.tab_width;
+                self.col = stops.wrapping_add(1).wrapping_mul(self.tab_width);
+            }
+            b"\x08" => self.col = self.col.saturating_sub(1),
+            // other C0 controls, DEL and C1 controls don't print anything
+            [0x00..=0x1f | 0x7f] | [0xc2, 0x80..=0x9f] => {}
+            _ => self.col = self.col.wrapping_add(self.column_mode.width(grapheme)),
+        }
+    }
+}
+
+/// The number of columns that a horizontal frequency of 1 takes to go through the whole flag
+pub const COLUMNS_PER_FLAG: u32 = 15;
+/// The number of lines that a vertical frequency of 1 takes to go through the whole flag
+pub const LINES_PER_FLAG: u32 = 30;
+
+/// Maps positions in the terminal to places on the flag, from 0 at the start of the flag to just below 1 at the
+/// end. Every colorizer moves through the flag with a `Phase`, so the same frequency, offset and geometry give the
+/// same pattern no matter how many colors the terminal has.
+///
+/// Places wrap around after the end of the flag, so no line or column is too far away.
+///
+/// # Examples
+/// ```
+/// use queercat_lib::{ColorV, Phase, Position, QueerCatFrequency};
+/// let phase = Phase::new(QueerCatFrequency::Horizontal(7.5)).with_offset(0.25);
+/// assert_eq!(phase.at(Position { line: 0, col: 0 }), ColorV::from_num(0.25));
+/// // half of the flag further every column
+/// assert_eq!(phase.at(Position { line: 9, col: 1 }), ColorV::from_num(0.75));
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct Phase {
+    /// The place at the start of the first line
+    offset: ColorV,
+    /// Flags per column
+    freq_h: SignedExtended,
+    /// Flags per line
+    freq_v: SignedExtended,
+    geometry: Geometry,
+}
+
+impl Phase {
+    /// Goes through the flag once every [`COLUMNS_PER_FLAG`] columns for every unit of the horizontal frequency,
+    /// and once every [`LINES_PER_FLAG`] lines for every unit of the vertical frequency
+    #[must_use]
+    pub fn new(freq: QueerCatFrequency) -> Self {
+        let (freq_h, freq_v) = freq.as_freq();
+        Self {
+            offset: ColorV::ZERO,
+            freq_h: freq_h / i64::from(COLUMNS_PER_FLAG),
+            freq_v: freq_v / i64::from(LINES_PER_FLAG),
+            geometry: Geometry::Linear,
+        }
+    }
+    /// A phase that starts at a pseudo-random offset picked by `seed`, which is the same for the same seed
+    #[must_use]
+    pub fn seeded(freq: QueerCatFrequency, seed: u64) -> Self {
+        Self::new(freq).with_seed(seed)
+    }
+    /// Adds `offset` whole flags to the offset. Negative offsets go the other way. Only the part after the last
+    /// whole flag matters, and offsets that aren't finite are ignored.
+    #[must_use]
+    pub fn with_offset(mut self, offset: f32) -> Self {
+        self.offset = self.offset.wrapping_add(wrap(f64::from(offset)));
+        self
+    }
+    /// Adds the pseudo-random offset that `seed` picks to the offset
+    #[must_use]
+    pub fn with_seed(mut self, seed: u64) -> Self {
+        self.offset = self.offset.wrapping_add(seed_offset(seed));
+        self
+    }
+    /// Sets the shape of the gradient. Defaults to [`Geometry::Linear`]
+    #[must_use]
+    pub fn with_geometry(mut self, geometry: Geometry) -> Self {
+        self.geometry = geometry;
+        self
+    }
+    /// The shape of the gradient
+    pub const fn geometry(&self) -> &Geometry {
+        &self.geometry
+    }
+    /// The 24 bit color of `flag` at `theta`, with the stripes of the stripes geometries blended like they ask for
+    pub fn color(&self, theta: ColorV, flag: &Flag<'_>) -> Color {
+        match self.geometry {
+            Geometry::Stripes { blend, .. } | Geometry::VerticalStripes { blend, .. } => {
+                Color::from_stripes(theta, flag, blend)
+            }
+            _ => Color::from_flag(theta, flag),
+        }
+    }
+    /// Whether the end of the flag blends into its start, which every geometry but the stripes does
+    fn wraps(&self) -> bool {
+        !matches!(
+            self.geometry,
+            Geometry::Stripes { .. } | Geometry::VerticalStripes { .. }
+        )
+    }
+    /// The index of the part of a flag with `len` parts that `theta` is in
+    fn index(theta: ColorV, len: usize) -> usize {
+        #[allow(clippy::cast_possible_truncation)]
+        let index = ((u64::from(theta.to_bits()) * len as u64) >> ColorV::FRAC_NBITS) as usize;
+        index
+    }
+    /// The place on the flag of the grapheme that ends at `position`
+    pub fn at(&self, position: Position) -> ColorV {
+        if let Geometry::Linear = self.geometry {
+            let col_theta = ColorV::wrapping_from_num(self.freq_h).wrapping_mul_int(position.col);
+            let line_theta = ColorV::wrapping_from_num(self.freq_v).wrapping_mul_int(position.line);
+            return self.offset.wrapping_add(line_theta).wrapping_add(col_theta);
+        }
+        let cycles = self
+            .geometry
+            .cycles(position, self.freq_h.to_num(), self.freq_v.to_num());
+        wrap(self.offset.to_num::<f64>() + cycles)
+    }
+}
 
-        (Extended::from_num(freq_h), Extended::from_num(freq_v))
+/// The place on the flag after going through it `flags` times, or the start of the flag when `flags` isn't finite
+fn wrap(flags: f64) -> ColorV {
+    if flags.is_finite() {
+        // `rem_euclid` rounds tiny negative numbers up to 1, which wraps around to 0
+        ColorV::wrapping_from_num(flags.rem_euclid(1.0))
+    } else {
+        ColorV::ZERO
     }
 }
 
@@ -47,40 +316,194 @@ impl std::fmt::Display for TerminalResetter {
     }
 }
 
-/// A colorizer that converts graphemes to a `State` that can be converted to a `Color` at any time 
+/// A colorizer that converts graphemes to a `State` that can be converted to a `Color` at any time
 pub trait Colorizer {
     type Color: std::fmt::Display + PartialEq<Self::Color> + Default;
     /// Resets the terminal colors when printed
     type Resetter: std::fmt::Display + Default;
     type State;
-    fn calculate_color(state: Self::State, flag: &Flag<'_>) -> Self::Color;
+    fn calculate_color(&self, state: Self::State, flag: &Flag<'_>) -> Self::Color;
+    /// Writes the escape that sets `color`. Colorizers with pre-rendered escapes can write them directly.
+    /// # Errors
+    /// Returns `Err` when writing to `writer` fails
+    fn write_color<W: Write>(&self, color: &Self::Color, writer: &mut W) -> io::Result<()> {
+        writer.write_fmt(format_args!("{color}"))
+    }
     /// Updates `self` and returns a `Self::State`
     fn update_state(&mut self, grapheme: &str) -> Self::State;
+    /// The `Self::State` at the current position, without updating `self`
+    fn state(&self) -> Self::State;
+    /// The position after the last grapheme
+    fn position(&self) -> Position;
+    /// Moves to `position` as if everything before it was colorized already.
+    /// A snapshot of the whole state can be taken by cloning the colorizer.
+    fn seek(&mut self, position: Position);
+}
+
+/// The place on the flag of a grapheme and the position that it ends at. Colorizers that dither need both.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Place {
+    pub theta: ColorV,
+    pub position: Position,
+}
+
+/// The colors that a colorizer with only a few colors gives the parts of a flag, in the order of the flag.
+/// It is made once per flag and shared between clones of the colorizer.
+#[derive(Debug, PartialEq)]
+struct Palette<T> {
+    entries: Vec<T>,
+    /// The 24 bit color of every entry, for dithering
+    colors: Vec<Color>,
+}
+
+impl<T: Copy> Palette<T> {
+    fn new(entries: Vec<T>, color: impl Fn(T) -> Color) -> Self {
+        let colors = entries.iter().map(|&entry| color(entry)).collect();
+        Self { entries, colors }
+    }
+
+    /// The entry of the grapheme at `place`. With `dither`, it is dithered towards the 24 bit color of `flag` there.
+    fn pick(&self, place: Place, phase: &Phase, dither: bool, flag: &Flag<'_>) -> T {
+        let len = self.entries.len();
+        let mut index = Phase::index(place.theta, len);
+        if dither {
+            let target = phase.color(place.theta, flag);
+            index = dither::pick(index, place.position, &self.colors, phase.wraps(), target);
+        }
+        self.entries[index]
+    }
+}
+
+/// Follows graphemes through the terminal with a [`Cursor`], and maps the positions that they end up at to places on
+/// the flag with a [`Phase`]. Every colorizer moves with one and shares its settings through [`Moving`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct Motion {
+    phase: Phase,
+    cursor: Cursor,
+}
+
+impl Motion {
+    /// Moves through the flag like `phase`, starting at the start of the first line
+    pub const fn new(phase: Phase) -> Self {
+        Self {
+            phase,
+            cursor: Cursor::new(),
+        }
+    }
+    /// The position after the last grapheme
+    pub const fn position(&self) -> Position {
+        self.cursor.position()
+    }
+    /// The place of a grapheme that ends at the current position
+    pub fn place(&self) -> Place {
+        let position = self.cursor.position();
+        Place {
+            theta: self.phase.at(position),
+            position,
+        }
+    }
+    /// Moves over `grapheme` and returns its place
+    pub fn advance(&mut self, grapheme: &str) -> Place {
+        self.cursor.advance(grapheme);
+        self.place()
+    }
+    /// Moves to `position`
+    pub fn seek(&mut self, position: Position) {
+        self.cursor.seek(position);
+    }
+}
+
+/// The settings of a colorizer that moves with a [`Motion`]
+pub trait Moving: Sized {
+    /// How the colorizer moves through the flag
+    fn motion(&self) -> &Motion;
+    /// Replaces the motion of the colorizer with what `f` makes of it
+    #[must_use]
+    fn map_motion(self, f: impl FnOnce(Motion) -> Motion) -> Self;
+
+    /// Adds `offset` whole flags to the offset. Negative offsets go the other way.
+    #[must_use]
+    fn with_offset(self, offset: f32) -> Self {
+        self.map_motion(|m| Motion {
+            phase: m.phase.with_offset(offset),
+            ..m
+        })
+    }
+    /// Adds the pseudo-random offset that `seed` picks to the offset
+    #[must_use]
+    fn with_seed(self, seed: u64) -> Self {
+        self.map_motion(|m| Motion {
+            phase: m.phase.with_seed(seed),
+            ..m
+        })
+    }
+    /// Sets how many columns each grapheme takes up
+    #[must_use]
+    fn with_column_mode(self, column_mode: ColumnMode) -> Self {
+        self.map_motion(|m| Motion {
+            cursor: m.cursor.with_column_mode(column_mode),
+            ..m
+        })
+    }
+    /// Sets the distance between tab stops
+    #[must_use]
+    fn with_tab_width(self, tab_width: u32) -> Self {
+        self.map_motion(|m| Motion {
+            cursor: m.cursor.with_tab_width(tab_width),
+            ..m
+        })
+    }
+    /// Sets the shape of the gradient. Defaults to [`Geometry::Linear`]
+    #[must_use]
+    fn with_geometry(self, geometry: Geometry) -> Self {
+        self.map_motion(|m| Motion {
+            phase: m.phase.with_geometry(geometry),
+            ..m
+        })
+    }
+    /// How positions are mapped to places on the flag
+    fn phase(&self) -> &Phase {
+        &self.motion().phase
+    }
+}
+
+impl Moving for Motion {
+    fn motion(&self) -> &Motion {
+        self
+    }
+    fn map_motion(self, f: impl FnOnce(Motion) -> Motion) -> Self {
+        f(self)
+    }
 }
 
 /// A 24bit colorizer
+#[derive(Debug, Clone, PartialEq)]
 pub struct Bits24 {
-    theta: ColorV,
-    col_theta: ColorV,
-    freq_v: Extended,
-    freq_h: Extended,
+    motion: Motion,
 }
 
 impl Bits24 {
     #[must_use]
     pub fn new(freq: QueerCatFrequency) -> Self {
-        let (freq_h, freq_v) = freq.as_freq();
+        Self::from_phase(Phase::new(freq))
+    }
+    /// A colorizer that moves through the flag like `phase`
+    #[must_use]
+    pub const fn from_phase(phase: Phase) -> Self {
         Self {
-            freq_h: freq_h / 15,
-            freq_v: freq_v / 30,
-            theta: ColorV::ZERO,
-            col_theta: ColorV::ZERO,
+            motion: Motion::new(phase),
         }
     }
-    #[must_use]
-    pub fn with_offset(mut self, offset: f32) -> Self {
-        self.theta += ColorV::wrapping_from_num(offset / 12.0);
-        self
+}
+
+impl Moving for Bits24 {
+    fn motion(&self) -> &Motion {
+        &self.motion
+    }
+    fn map_motion(self, f: impl FnOnce(Motion) -> Motion) -> Self {
+        Self {
+            motion: f(self.motion),
+        }
     }
 }
 
@@ -89,81 +512,384 @@ impl Colorizer for Bits24 {
     type Color = Color;
     type Resetter = TerminalResetter;
 
-    fn calculate_color(state: Self::State, flag: &Flag<'_>) -> Self::Color {
-        match flag.color_method {
-            crate::ColorMethod::Rainbow => Color::rainbow(state),
-            crate::ColorMethod::Stripes => {
-                let stripe_size = Extended::from_num(flag.stripe_colors.len()).recip();
-                let stripe_size = ColorV::wrapping_from_num(stripe_size);
-                Color::stripe(state, flag, stripe_size)
-            }
-        }
+    fn calculate_color(&self, state: Self::State, flag: &Flag<'_>) -> Self::Color {
+        self.motion.phase.color(state, flag)
     }
     fn update_state(&mut self, grapheme: &str) -> Self::State {
-        if let [b'\n'] = grapheme.as_bytes() {
-            self.theta = self
-                .theta
-                .wrapping_add(ColorV::wrapping_from_num(self.freq_v));
-            self.theta = self.theta.wrapping_sub(self.col_theta);
-            self.col_theta = ColorV::ZERO;
-        } else {
-            let theta = ColorV::wrapping_from_num(self.freq_h);
-            self.col_theta = self.col_theta.wrapping_add(theta);
-            self.theta = self.theta.wrapping_add(theta);
+        self.motion.advance(grapheme).theta
+    }
+    fn state(&self) -> Self::State {
+        self.motion.place().theta
+    }
+    fn position(&self) -> Position {
+        self.motion.position()
+    }
+    fn seek(&mut self, position: Position) {
+        self.motion.seek(position);
+    }
+}
+
+/// A 24bit colorizer that looks colors up in a [`Gradient`] instead of calculating them, and writes their
+/// pre-rendered escapes. The gradient is shared between clones.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Bits24Lut {
+    bits24: Bits24,
+    gradient: Arc<Gradient>,
+}
+
+impl Bits24Lut {
+    /// Moves like `bits24` and colorizes with the colors from `gradient`.
+    /// The gradient has to be made from the flag that the colorizer is used with. For the stripes geometries, sample
+    /// the colors of `bits24` with [`Gradient::from_fn`] instead of using [`Gradient::new`].
+    #[must_use]
+    pub fn new(bits24: Bits24, gradient: impl Into<Arc<Gradient>>) -> Self {
+        Self {
+            bits24,
+            gradient: gradient.into(),
         }
-        self.theta
+    }
+    /// The gradient that colors are looked up in
+    pub fn gradient(&self) -> &Gradient {
+        &self.gradient
     }
 }
 
-/// An Ansi colorizer
+impl Moving for Bits24Lut {
+    fn motion(&self) -> &Motion {
+        &self.bits24.motion
+    }
+    fn map_motion(self, f: impl FnOnce(Motion) -> Motion) -> Self {
+        Self {
+            bits24: self.bits24.map_motion(f),
+            ..self
+        }
+    }
+}
+
+impl Colorizer for Bits24Lut {
+    type State = ColorV;
+    type Color = GradientColor;
+    type Resetter = TerminalResetter;
+
+    fn calculate_color(&self, state: Self::State, _flag: &Flag<'_>) -> Self::Color {
+        let index = self.gradient.index(state);
+        GradientColor {
+            index,
+            color: self.gradient.color(index),
+        }
+    }
+    fn write_color<W: Write>(&self, color: &Self::Color, writer: &mut W) -> io::Result<()> {
+        writer.write_all(self.gradient.escape(color.index))
+    }
+    fn update_state(&mut self, grapheme: &str) -> Self::State {
+        self.bits24.update_state(grapheme)
+    }
+    fn state(&self) -> Self::State {
+        self.bits24.state()
+    }
+    fn position(&self) -> Position {
+        self.bits24.position()
+    }
+    fn seek(&mut self, position: Position) {
+        self.bits24.seek(position);
+    }
+}
+
+/// An Ansi colorizer. It moves through the flag exactly like [`Bits24`] does, but every place on the flag gets the
+/// ansi color of the part of `ansi_colors` that it is in. Flags without `ansi_colors` get them picked with
+/// [`Flag::derive_ansi_colors`]. The colors are picked once, so the colorizer has to be used with the flag that it was
+/// made for.
+#[derive(Debug, Clone, PartialEq)]
 pub struct Ansi {
-    line: u32,
-    col: u32,
-    flag_len: u32,
-    offset: Extended,
-    freq_v: Extended,
-    freq_h: Extended,
+    motion: Motion,
+    palette: Arc<Palette<AnsiColor>>,
+    dither: bool,
 }
 
 impl Colorizer for Ansi {
     type Color = AnsiColor;
     type Resetter = TerminalResetter;
-    type State = u32;
+    type State = Place;
 
-    fn calculate_color(ansi_index: Self::State, flag: &Flag<'_>) -> Self::Color {
-        AnsiColor(flag.ansi_colors[ansi_index as usize])
+    fn calculate_color(&self, place: Self::State, flag: &Flag<'_>) -> Self::Color {
+        self.palette
+            .pick(place, &self.motion.phase, self.dither, flag)
     }
-
     fn update_state(&mut self, grapheme: &str) -> Self::State {
-        if let [b'\n'] = grapheme.as_bytes() {
-            self.col = 0;
-            self.line += 1;
+        self.motion.advance(grapheme)
+    }
+    fn state(&self) -> Self::State {
+        self.motion.place()
+    }
+    fn position(&self) -> Position {
+        self.motion.position()
+    }
+    fn seek(&mut self, position: Position) {
+        self.motion.seek(position);
+    }
+}
+
+impl Ansi {
+    #[must_use]
+    pub fn new(flag: &Flag<'_>, freq: QueerCatFrequency) -> Self {
+        Self::from_phase(flag, Phase::new(freq))
+    }
+    /// A colorizer for `flag` that moves through it like `phase`
+    #[must_use]
+    pub fn from_phase(flag: &Flag<'_>, phase: Phase) -> Self {
+        let entries = if flag.ansi_colors.is_empty() {
+            flag.derive_ansi_colors()
         } else {
-            self.col += 1;
+            flag.ansi_colors.to_vec()
+        };
+        let entries = entries.into_iter().map(AnsiColor).collect();
+        Self {
+            motion: Motion::new(phase),
+            palette: Arc::new(Palette::new(entries, AnsiColor::color)),
+            dither: false,
         }
-        (((self.offset.wrapping_mul_int(self.flag_len))
-            + (self.col * self.freq_v + self.line * self.freq_h))
-            .to_num::<u32>())
-            % self.flag_len
+    }
+    /// Sets whether blends between the ansi colors of the flag are dithered like in 24 bit mode, instead of turning
+    /// into hard bands. Defaults to `false`
+    #[must_use]
+    pub const fn with_dither(mut self, dither: bool) -> Self {
+        self.dither = dither;
+        self
     }
 }
 
-impl Ansi {
+impl Moving for Ansi {
+    fn motion(&self) -> &Motion {
+        &self.motion
+    }
+    fn map_motion(self, f: impl FnOnce(Motion) -> Motion) -> Self {
+        Self {
+            motion: f(self.motion),
+            ..self
+        }
+    }
+}
+
+/// A colorizer that only uses the 16 basic ansi colors, for terminals like the Linux console. It moves through the
+/// flag exactly like [`Bits24`] does. Flags with [`Flag::basic_colors`] use them like [`Ansi`] uses `ansi_colors`,
+/// and other flags get the basic colors that look the most like their stripes. The colors are picked once, so the
+/// colorizer has to be used with the flag that it was made for.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Basic {
+    motion: Motion,
+    palette: Arc<Palette<BasicColor>>,
+    dither: bool,
+}
+
+impl Colorizer for Basic {
+    type Color = BasicColor;
+    type Resetter = TerminalResetter;
+    type State = Place;
+
+    fn calculate_color(&self, place: Self::State, flag: &Flag<'_>) -> Self::Color {
+        self.palette
+            .pick(place, &self.motion.phase, self.dither, flag)
+    }
+    fn update_state(&mut self, grapheme: &str) -> Self::State {
+        self.motion.advance(grapheme)
+    }
+    fn state(&self) -> Self::State {
+        self.motion.place()
+    }
+    fn position(&self) -> Position {
+        self.motion.position()
+    }
+    fn seek(&mut self, position: Position) {
+        self.motion.seek(position);
+    }
+}
+
+impl Basic {
     #[must_use]
-    pub fn new(flag_len: u32, freq: QueerCatFrequency) -> Self {
-        let (freq_h, freq_v) = freq.as_freq();
+    pub fn new(flag: &Flag<'_>, freq: QueerCatFrequency) -> Self {
+        Self::from_phase(flag, Phase::new(freq))
+    }
+    /// A colorizer for `flag` that moves through it like `phase`
+    #[must_use]
+    pub fn from_phase(flag: &Flag<'_>, phase: Phase) -> Self {
+        // without a table, every stripe, or every part of a rainbow, gets the basic color closest to it
+        let entries = if !flag.basic_colors.is_empty() {
+            flag.basic_colors
+                .iter()
+                .map(|&code| BasicColor(code))
+                .collect()
+        } else if !flag.is_rainbow() {
+            flag.stripe_colors
+                .iter()
+                .map(|&color| BasicColor::nearest(color))
+                .collect()
+        } else {
+            (0..RAINBOW_ANSI_COLORS)
+                .map(|i| BasicColor::nearest(Color::rainbow(middle(i, RAINBOW_ANSI_COLORS))))
+                .collect()
+        };
         Self {
-            freq_h,
-            freq_v,
-            flag_len,
-            line: 0,
-            col: 0,
-            offset: Extended::ZERO,
+            motion: Motion::new(phase),
+            palette: Arc::new(Palette::new(entries, BasicColor::color)),
+            dither: false,
         }
     }
+    /// Sets whether blends between the basic colors are dithered like in 24 bit mode, instead of turning into hard
+    /// bands. Defaults to `false`
     #[must_use]
-    pub fn with_offset(self, offset: f32) -> Self {
-        let offset = Extended::from_num(offset);
-        Self { offset, ..self }
+    pub const fn with_dither(mut self, dither: bool) -> Self {
+        self.dither = dither;
+        self
+    }
+}
+
+impl Moving for Basic {
+    fn motion(&self) -> &Motion {
+        &self.motion
+    }
+    fn map_motion(self, f: impl FnOnce(Motion) -> Motion) -> Self {
+        Self {
+            motion: f(self.motion),
+            ..self
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The position after `graphemes`
+    fn after(cursor: Cursor, graphemes: &[&str]) -> (u32, u32) {
+        let mut cursor = cursor;
+        for grapheme in graphemes {
+            cursor.advance(grapheme);
+        }
+        (cursor.line(), cursor.col())
+    }
+
+    #[test]
+    fn tabs_align_after_wide_graphemes() {
+        let cursor = Cursor::new();
+        assert_eq!(after(cursor, &["漢", "\t"]), (0, 8));
+        assert_eq!(after(cursor, &["a", "漢", "\t", "x"]), (0, 9));
+        assert_eq!(after(cursor, &["漢", "漢", "漢", "漢", "\t"]), (0, 16));
+        assert_eq!(after(cursor, &["👩‍👩‍👧", "e\u{301}", "\t"]), (0, 8));
+        let narrow = cursor.with_column_mode(ColumnMode::Graphemes);
+        assert_eq!(after(narrow, &["漢", "漢", "漢", "漢", "\t"]), (0, 8));
+        let cursor = cursor.with_tab_width(4);
+        assert_eq!(after(cursor, &["漢", "a", "\t"]), (0, 4));
+        assert_eq!(after(cursor, &["漢", "漢", "\t"]), (0, 8));
+        // a width of 0 is treated as 1
+        let cursor = cursor.with_tab_width(0);
+        assert_eq!(after(cursor, &["漢", "\t", "\t"]), (0, 4));
+    }
+
+    #[test]
+    fn carriage_returns_reset_the_column() {
+        let cursor = Cursor::new();
+        assert_eq!(after(cursor, &["a", "漢", "\r"]), (0, 0));
+        assert_eq!(after(cursor, &["a", "\r", "b"]), (0, 1));
+        assert_eq!(after(cursor, &["a", "\n", "b", "\r", "\r"]), (1, 0));
+        assert_eq!(after(cursor, &["a", "b", "\r\n", "c"]), (1, 1));
+        assert_eq!(after(cursor, &["a", "\t", "\r", "\t"]), (0, 8));
+    }
+
+    #[test]
+    fn backspaces_stop_at_the_start_of_the_line() {
+        let cursor = Cursor::new();
+        assert_eq!(after(cursor, &["\x08"]), (0, 0));
+        assert_eq!(after(cursor, &["a", "\x08", "\x08", "b"]), (0, 1));
+        assert_eq!(after(cursor, &["漢", "\x08"]), (0, 1));
+        assert_eq!(after(cursor, &["a", "\n", "\x08"]), (1, 0));
+    }
+
+    #[test]
+    fn frequencies_that_dont_fit_are_saturated() {
+        let (max, min) = (SignedExtended::MAX, SignedExtended::MIN);
+        let zero = SignedExtended::ZERO;
+        for (freq, expected) in [
+            (QueerCatFrequency::Custom(f32::NAN, f32::NAN), (zero, zero)),
+            (
+                QueerCatFrequency::Custom(f32::INFINITY, f32::NEG_INFINITY),
+                (min, max),
+            ),
+            (QueerCatFrequency::Custom(1e12, -1e12), (min, max)),
+            (
+                QueerCatFrequency::Horizontal(2.5),
+                (SignedExtended::from_num(2.5), zero),
+            ),
+        ] {
+            assert_eq!(freq.as_freq(), expected, "{freq:?}");
+            // every position still has a place
+            let phase = Phase::new(freq);
+            phase.at(Position { line: 7, col: 3 });
+            phase
+                .with_geometry(Geometry::Angle(30.0))
+                .at(Position { line: 7, col: 3 });
+        }
+    }
+
+    #[test]
+    fn offsets_wrap_around() {
+        let start = Position { line: 0, col: 0 };
+        let at = |offset: f32| {
+            Phase::new(QueerCatFrequency::Original)
+                .with_offset(offset)
+                .at(start)
+        };
+        assert_eq!(at(0.25), ColorV::from_num(0.25));
+        assert_eq!(at(-0.75), ColorV::from_num(0.25));
+        assert_eq!(at(3.25), ColorV::from_num(0.25));
+        assert_eq!(at(1e12), ColorV::ZERO);
+        assert_eq!(at(-1e12), ColorV::ZERO);
+        assert_eq!(at(-1e-10), ColorV::ZERO);
+        for offset in [f32::NAN, f32::INFINITY, f32::NEG_INFINITY] {
+            assert_eq!(at(offset), ColorV::ZERO, "{offset}");
+        }
+        // and so do places far into the flag with other geometries
+        let angled =
+            Phase::new(QueerCatFrequency::Custom(1e12, 1e12)).with_geometry(Geometry::Angle(45.0));
+        angled.with_offset(f32::MAX).at(Position {
+            line: u32::MAX,
+            col: u32::MAX,
+        });
+    }
+
+    #[test]
+    fn seeds_pick_the_same_offset_everywhere() {
+        // the first outputs of splitmix64, which don't depend on the platform
+        assert_eq!(seed_offset(0).to_bits(), 0xe220_a839);
+        assert_eq!(seed_offset(1).to_bits(), 0x910a_2dec);
+        assert_eq!(seed_offset(42).to_bits(), 0xbdd7_3226);
+
+        let freq = QueerCatFrequency::Original;
+        let position = Position { line: 3, col: 17 };
+        let seeded = |seed| Phase::seeded(freq, seed).at(position);
+        assert_eq!(seeded(42), seeded(42));
+        assert_eq!(
+            seeded(42),
+            Phase::new(freq).at(position).wrapping_add(seed_offset(42))
+        );
+        let cat = |seed| {
+            let mut output = Vec::new();
+            crate::QueerCat::new(
+                Bits24::new(freq).with_seed(seed),
+                &mut output,
+                crate::transgender(),
+            )
+            .cat(&b"trans rights are human rights\n"[..])
+            .map(|()| output)
+            .unwrap()
+        };
+        assert_eq!(cat(7), cat(7));
+        assert_ne!(cat(7), cat(8));
+        // seeds that are close together still start far apart
+        let offsets: Vec<ColorV> = (0..16).map(seed_offset).collect();
+        for (i, a) in offsets.iter().enumerate() {
+            for b in &offsets[i + 1..] {
+                assert!(a.wrapping_sub(*b).min(b.wrapping_sub(*a)) > ColorV::from_num(0.001));
+            }
+        }
     }
 }
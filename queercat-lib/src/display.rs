@@ -0,0 +1,158 @@
+//! Colorizing text that is already in memory
+//!
+//! # Examples
+//! ```
+//! use queercat_lib::{colorize, lesbian, ColorizeOptions, QueerExt};
+//! println!("{}", "hello world".queer(lesbian()));
+//! let banner: String = colorize("welcome!", &lesbian(), ColorizeOptions::default());
+//! assert!(banner.starts_with("\x1b[38;5;"));
+//! ```
+
+use crate::colorizer::{
+    Ansi, Basic, Bits24, ColumnMode, Moving, QueerCatFrequency, DEFAULT_TAB_WIDTH,
+};
+use crate::driver::{EscapeMode, Granularity, Painter};
+use crate::flag::Flag;
+use crate::Colorizer;
+use std::fmt;
+use std::io::{self, Write};
+
+/// The kinds of colors that can be written
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ColorDepth {
+    /// 256 color ansi codes, using [`Ansi`]
+    #[default]
+    Ansi,
+    /// 24 bit RGB colors, using [`Bits24`]
+    Bits24,
+    /// The 16 basic ansi colors, using [`Basic`]
+    Basic,
+}
+
+/// Settings for colorizing text in memory
+#[derive(Debug, Clone, Copy)]
+pub struct ColorizeOptions {
+    pub depth: ColorDepth,
+    pub frequency: QueerCatFrequency,
+    pub offset: f32,
+    pub escape_mode: EscapeMode,
+    pub column_mode: ColumnMode,
+    pub tab_width: u32,
+    pub granularity: Granularity,
+}
+
+impl Default for ColorizeOptions {
+    fn default() -> Self {
+        Self {
+            depth: ColorDepth::default(),
+            frequency: QueerCatFrequency::Original,
+            offset: 0.0,
+            escape_mode: EscapeMode::default(),
+            column_mode: ColumnMode::default(),
+            tab_width: DEFAULT_TAB_WIDTH,
+            granularity: Granularity::default(),
+        }
+    }
+}
+
+/// Text that is colorized when it is formatted
+#[derive(Debug, Clone)]
+pub struct Queer<'a> {
+    text: &'a str,
+    flag: Flag<'a>,
+    options: ColorizeOptions,
+}
+
+impl<'a> Queer<'a> {
+    #[must_use]
+    pub fn new(text: &'a str, flag: Flag<'a>) -> Self {
+        Self {
+            text,
+            flag,
+            options: ColorizeOptions::default(),
+        }
+    }
+
+    /// Sets the options to colorize the text with
+    #[must_use]
+    pub fn with_options(self, options: ColorizeOptions) -> Self {
+        Self { options, ..self }
+    }
+
+    fn write_with<C: Colorizer>(&self, colorizer: C, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut writer = FmtWriter(f);
+        let mut painter = Painter::new(colorizer, self.flag.clone());
+        painter.escape_mode = self.options.escape_mode;
+        painter.set_granularity(self.options.granularity);
+        painter
+            .write(self.text.as_bytes(), &mut writer)
+            .and_then(|()| painter.finish(&mut writer))
+            .and_then(|()| painter.flush(&mut writer))
+            .map_err(|_| fmt::Error)
+    }
+}
+
+impl fmt::Display for Queer<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let options = &self.options;
+        match options.depth {
+            ColorDepth::Ansi => {
+                let colorizer = Ansi::new(&self.flag, options.frequency)
+                    .with_offset(options.offset)
+                    .with_column_mode(options.column_mode)
+                    .with_tab_width(options.tab_width);
+                self.write_with(colorizer, f)
+            }
+            ColorDepth::Bits24 => {
+                let colorizer = Bits24::new(options.frequency)
+                    .with_offset(options.offset)
+                    .with_column_mode(options.column_mode)
+                    .with_tab_width(options.tab_width);
+                self.write_with(colorizer, f)
+            }
+            ColorDepth::Basic => {
+                let colorizer = Basic::new(&self.flag, options.frequency)
+                    .with_offset(options.offset)
+                    .with_column_mode(options.column_mode)
+                    .with_tab_width(options.tab_width);
+                self.write_with(colorizer, f)
+            }
+        }
+    }
+}
+
+/// Writes to a `Formatter`. Only valid utf-8 is ever written to it, since the input is a `str`.
+struct FmtWriter<'a, 'b>(&'a mut fmt::Formatter<'b>);
+
+impl Write for FmtWriter<'_, '_> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let s =
+            std::str::from_utf8(buf).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        self.0.write_str(s).map_err(io::Error::other)?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Colorizes `text` with the pattern of `flag`
+#[must_use]
+pub fn colorize(text: &str, flag: &Flag<'_>, options: ColorizeOptions) -> String {
+    Queer::new(text, flag.clone())
+        .with_options(options)
+        .to_string()
+}
+
+/// Colorizes text when it is formatted, in the style of `owo-colors`
+pub trait QueerExt {
+    /// Colorizes `self` with the pattern of `flag`
+    fn queer<'a>(&'a self, flag: Flag<'a>) -> Queer<'a>;
+}
+
+impl<T: AsRef<str> + ?Sized> QueerExt for T {
+    fn queer<'a>(&'a self, flag: Flag<'a>) -> Queer<'a> {
+        Queer::new(self.as_ref(), flag)
+    }
+}
@@ -0,0 +1,154 @@
+//! Ordered dithering for colorizers with only a few colors. Where the 24 bit gradient blends two colors of the
+//! palette, graphemes get one or the other in a Bayer matrix pattern, so that the blend looks smooth from afar
+//! instead of turning into hard bands.
+
+use crate::color::Color;
+use crate::colorizer::Position;
+
+/// The order in which the cells of a 4x4 block pick the second color as it gets stronger
+const BAYER: [[u8; 4]; 4] = [[0, 8, 2, 10], [12, 4, 14, 6], [3, 11, 1, 9], [15, 7, 13, 5]];
+
+/// How much of the second color it takes for the grapheme at `position` to get it, between 0 and 1
+fn threshold(position: Position) -> f32 {
+    let cell = BAYER[position.line as usize % 4][position.col as usize % 4];
+    (f32::from(cell) + 0.5) / 16.0
+}
+
+/// How far `target` is along the way from `from` to `to`, between 0 and 1
+fn progress(target: Color, from: Color, to: Color) -> f32 {
+    // the raw bits convert much faster than the fixed point numbers, and the scale cancels out
+    let channels = |c: Color| [c.red(), c.green(), c.blue()].map(|v| f64::from(v.to_bits()));
+    let (target, from, to) = (channels(target), channels(from), channels(to));
+    let (mut along, mut length) = (0.0, 0.0);
+    for i in 0..3 {
+        along += (target[i] - from[i]) * (to[i] - from[i]);
+        length += (to[i] - from[i]).powi(2);
+    }
+    if length == 0.0 {
+        0.0
+    } else {
+        #[allow(clippy::cast_possible_truncation)]
+        let progress = (along / length).clamp(0.0, 1.0) as f32;
+        progress
+    }
+}
+
+/// The index of the closest entry of `colors` before or after `index` that has another color than `current`
+fn neighbor(
+    index: usize,
+    forward: bool,
+    colors: &[Color],
+    wrap: bool,
+    current: Color,
+) -> Option<usize> {
+    let len = colors.len();
+    let mut i = index;
+    for _ in 1..len {
+        i = match (forward, wrap) {
+            (true, _) if i + 1 < len => i + 1,
+            (true, true) => 0,
+            (false, _) if i > 0 => i - 1,
+            (false, true) => len - 1,
+            _ => return None,
+        };
+        if colors[i] != current {
+            return Some(i);
+        }
+    }
+    None
+}
+
+/// Picks an entry of a palette with the colors `colors` in the order of the flag for the grapheme at `position`,
+/// where the 24 bit gradient is `target`. `index` is the entry that the grapheme is in, and it is swapped for one of
+/// the closest entries with another color as often as `target` is blended with it. With `wrap`, the first and last
+/// entries are next to each other.
+pub(crate) fn pick(
+    index: usize,
+    position: Position,
+    colors: &[Color],
+    wrap: bool,
+    target: Color,
+) -> usize {
+    let current = colors[index];
+    // most graphemes of flags with hard stripes are right on the color of their entry
+    if target == current {
+        return index;
+    }
+    let best = [true, false]
+        .into_iter()
+        .filter_map(|forward| neighbor(index, forward, colors, wrap, current))
+        .map(|i| (i, progress(target, current, colors[i])))
+        .max_by(|(_, a), (_, b)| a.total_cmp(b));
+    match best {
+        Some((i, amount)) if amount > threshold(position) => i,
+        _ => index,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The positions of a 4x4 block
+    fn block() -> impl Iterator<Item = Position> {
+        (0..4).flat_map(|line| (0..4).map(move |col| Position { line, col }))
+    }
+
+    #[test]
+    fn thresholds_spread_over_every_block() {
+        let mut thresholds: Vec<f32> = block().map(threshold).collect();
+        thresholds.sort_by(f32::total_cmp);
+        let expected: Vec<f32> = (0..16u8).map(|i| (f32::from(i) + 0.5) / 16.0).collect();
+        assert_eq!(thresholds, expected);
+        // every quarter of the cells has one of each 2x2 square, so partial blends don't clump together
+        for quarter in 0..4 {
+            let mut squares: Vec<(u32, u32)> = block()
+                .filter(|p| BAYER[p.line as usize][p.col as usize] / 4 == quarter)
+                .map(|p| (p.line / 2, p.col / 2))
+                .collect();
+            squares.sort_unstable();
+            assert_eq!(squares, [(0, 0), (0, 1), (1, 0), (1, 1)]);
+        }
+        // positions repeat every 4 lines and columns
+        let far = Position {
+            line: 4001,
+            col: 4002,
+        };
+        let near = Position { line: 1, col: 2 };
+        assert_eq!(threshold(far).to_bits(), threshold(near).to_bits());
+    }
+
+    #[test]
+    fn two_colors_are_picked_as_often_as_they_are_blended() {
+        let colors = [Color::from_hex(0x00_00_00), Color::from_hex(0xff_ff_ff)];
+        for (target, count) in [
+            (0x00_00_00, 0),
+            (0x40_40_40, 4),
+            (0x80_80_80, 8),
+            (0xc0_c0_c0, 12),
+            (0xff_ff_ff, 16),
+        ] {
+            let target = Color::from_hex(target);
+            let picked = block()
+                .filter(|&p| pick(0, p, &colors, false, target) == 1)
+                .count();
+            assert_eq!(picked, count, "{target:?}");
+        }
+        // without another color to blend with, the entry stays
+        assert_eq!(
+            pick(0, Position::default(), &colors[..1], true, colors[1]),
+            0
+        );
+        let same = [colors[0], colors[0]];
+        assert_eq!(pick(0, Position::default(), &same, true, colors[1]), 0);
+    }
+
+    #[test]
+    fn neighbors_wrap_around_only_when_asked() {
+        let colors = [0xff_00_00, 0xff_00_00, 0x00_ff_00, 0x00_00_ff].map(Color::from_hex);
+        assert_eq!(neighbor(1, true, &colors, false, colors[1]), Some(2));
+        assert_eq!(neighbor(1, false, &colors, false, colors[1]), None);
+        assert_eq!(neighbor(1, false, &colors, true, colors[1]), Some(3));
+        assert_eq!(neighbor(3, true, &colors, true, colors[3]), Some(0));
+    }
+}
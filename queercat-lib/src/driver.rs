@@ -1,125 +1,796 @@
 //! The main driver for queercat
 
-use crate::colorizer::Colorizer;
+use crate::colorizer::{Colorizer, Position};
+use crate::escape::{Event, Parser, Sequence, DEFAULT_SEQUENCE_LIMIT};
 use crate::flag::Flag;
 use std::io::{self, BufRead, Write};
 
 use unicode_segmentation::UnicodeSegmentation;
 
-/// The main driver struct
-pub struct QueerCat<'a, W: Write, C: Colorizer> {
+/// How escape sequences that are already in the input are handled
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum EscapeMode {
+    /// Remove every escape sequence from the input
+    #[default]
+    Strip,
+    /// Forward escape sequences to the output. Only the foreground color parameters of SGR sequences are removed,
+    /// so styles like bold, underline and reverse video are kept while the flag decides the text color.
+    Passthrough,
+}
+
+/// What happens to input that is not valid utf-8
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum InvalidUtf8 {
+    /// Replace every invalid sequence with `U+FFFD REPLACEMENT CHARACTER`, which is colorized like any other character
+    #[default]
+    Replace,
+    /// Write invalid bytes to the output unchanged without colorizing them
+    Raw,
+    /// Stop and return an [`InvalidUtf8Error`]
+    Error,
+}
+
+/// How much of the text gets the same color. A unit is colorized with the color of its first grapheme, so the colors
+/// still follow the flag from unit to unit.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Granularity {
+    /// Every grapheme gets its own color
+    #[default]
+    Grapheme,
+    /// Every word that is separated by whitespace gets one color
+    Word,
+    /// Every line gets one color
+    Line,
+    /// Every paragraph that is separated by blank lines gets one color
+    Paragraph,
+}
+
+/// Finds the graphemes that start a new unit of a [`Granularity`], and keeps the color of the current unit
+#[derive(Clone, Copy)]
+struct Units<T> {
+    granularity: Granularity,
+    /// Whether the last grapheme was a line break
+    after_break: bool,
+    /// Whether there was whitespace since the last grapheme that wasn't whitespace
+    after_space: bool,
+    /// The number of line breaks since the last grapheme that wasn't whitespace
+    breaks: u32,
+    /// The color of the first grapheme of the current unit
+    color: Option<T>,
+}
+
+impl<T> Units<T> {
+    const fn new(granularity: Granularity) -> Self {
+        Self {
+            granularity,
+            after_break: false,
+            after_space: false,
+            breaks: 0,
+            color: None,
+        }
+    }
+
+    /// Whether `grapheme` starts a new unit. Moves past `grapheme`.
+    fn starts(&mut self, grapheme: &str) -> bool {
+        if self.granularity == Granularity::Grapheme {
+            return true;
+        }
+        let is_break = matches!(grapheme.as_bytes(), b"\n" | b"\r\n");
+        let is_space = grapheme.chars().next().is_some_and(char::is_whitespace);
+        let starts = match self.granularity {
+            Granularity::Grapheme => true,
+            Granularity::Word => !is_space && self.after_space,
+            Granularity::Line => self.after_break,
+            Granularity::Paragraph => !is_space && self.breaks >= 2,
+        };
+        self.after_break = is_break;
+        self.after_space = is_space;
+        if is_space {
+            self.breaks += u32::from(is_break);
+        } else {
+            self.breaks = 0;
+        }
+        starts
+    }
+}
+
+/// The error for input that is not valid utf-8 when using [`InvalidUtf8::Error`].
+/// It is returned as the inner error of an [`io::Error`] with the kind [`io::ErrorKind::InvalidData`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InvalidUtf8Error {
+    bytes: Vec<u8>,
+}
+
+impl InvalidUtf8Error {
+    /// The invalid bytes
+    #[must_use]
+    pub fn bytes(&self) -> &[u8] {
+        &self.bytes
+    }
+}
+
+impl std::fmt::Display for InvalidUtf8Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "invalid utf-8 sequence {:02x?}", self.bytes)
+    }
+}
+
+impl std::error::Error for InvalidUtf8Error {}
+
+/// An incomplete code point. Code points are at most 4 bytes long, so it always fits without allocating.
+#[derive(Clone, Copy)]
+struct Partial {
+    bytes: [u8; 4],
+    len: usize,
+}
+
+impl Partial {
+    const EMPTY: Self = Self {
+        bytes: [0; 4],
+        len: 0,
+    };
+
+    fn new(bytes: &[u8]) -> Self {
+        let mut partial = Self::EMPTY;
+        partial.bytes[..bytes.len()].copy_from_slice(bytes);
+        partial.len = bytes.len();
+        partial
+    }
+
+    fn as_bytes(&self) -> &[u8] {
+        &self.bytes[..self.len]
+    }
+
+    const fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+}
+
+/// The colorizing state machine that is shared by [`QueerCat`] and [`ColorWriter`](crate::ColorWriter).
+/// It is fed chunks of input and keeps unfinished escapes, code points and graphemes until the next chunk.
+pub(crate) struct Painter<'a, C: Colorizer> {
     colorizer: C,
-    writer: W,
     flag: Flag<'a>,
+    pub(crate) escape_mode: EscapeMode,
+    pub(crate) invalid_utf8: InvalidUtf8,
+    /// Whether the colors of the terminal are reset at the end
+    pub(crate) reset: bool,
+    units: Units<C::Color>,
+    parser: Parser,
+    /// An incomplete code point at the end of the last chunk
+    remaining: Partial,
+    /// The last grapheme of the last chunk, which might continue in the next chunk
+    carry: String,
+    /// How much of `carry` was already written by `flush`
+    carry_written: usize,
+    /// The position before `carry` when some of it was already written
+    carry_start: Position,
+    /// Whether the color of the current unit was written since the last reset
+    colored: bool,
 }
 
-impl<'a, W: Write, C: Colorizer> QueerCat<'a, W, C> {
-    #[must_use]
-    pub const fn new(colorizer: C, writer: W, flag: Flag<'a>) -> Self {
+/// The builders and colorizer accessors of a driver with a `painter` field. The doc comment is for `with_reset`,
+/// since when the colors are reset differs between the drivers.
+macro_rules! painter_builders {
+    ($(#[$reset_doc:meta])*) => {
+        /// Sets how escape sequences in the input are handled. Defaults to [`EscapeMode::Strip`]
+        ///
+        /// [`EscapeMode::Strip`]: crate::EscapeMode::Strip
+        #[must_use]
+        pub const fn with_escape_mode(mut self, escape_mode: crate::driver::EscapeMode) -> Self {
+            self.painter.escape_mode = escape_mode;
+            self
+        }
+
+        /// Sets what happens to input that is not valid utf-8. Defaults to [`InvalidUtf8::Replace`]
+        ///
+        /// [`InvalidUtf8::Replace`]: crate::InvalidUtf8::Replace
+        #[must_use]
+        pub const fn with_invalid_utf8(mut self, invalid_utf8: crate::driver::InvalidUtf8) -> Self {
+            self.painter.invalid_utf8 = invalid_utf8;
+            self
+        }
+
+        /// Sets how much of the text gets the same color. Defaults to [`Granularity::Grapheme`]
+        ///
+        /// [`Granularity::Grapheme`]: crate::Granularity::Grapheme
+        #[must_use]
+        pub fn with_granularity(mut self, granularity: crate::driver::Granularity) -> Self {
+            self.painter.set_granularity(granularity);
+            self
+        }
+
+        $(#[$reset_doc])*
+        #[must_use]
+        pub const fn with_reset(mut self, reset: bool) -> Self {
+            self.painter.reset = reset;
+            self
+        }
+
+        /// The colorizer, which is at the position after the last colorized grapheme
+        pub const fn colorizer(&self) -> &C {
+            self.painter.colorizer()
+        }
+
+        /// A mutable reference to the colorizer, for restoring a snapshot or seeking with [`Colorizer::seek`]
+        ///
+        /// [`Colorizer::seek`]: crate::Colorizer::seek
+        pub fn colorizer_mut(&mut self) -> &mut C {
+            self.painter.colorizer_mut()
+        }
+    };
+}
+pub(crate) use painter_builders;
+
+impl<'a, C: Colorizer> Painter<'a, C> {
+    pub(crate) const fn new(colorizer: C, flag: Flag<'a>) -> Self {
         Self {
             colorizer,
-            writer,
             flag,
+            escape_mode: EscapeMode::Strip,
+            invalid_utf8: InvalidUtf8::Replace,
+            reset: true,
+            units: Units::new(Granularity::Grapheme),
+            parser: Parser::new(),
+            remaining: Partial::EMPTY,
+            carry: String::new(),
+            carry_written: 0,
+            carry_start: Position { line: 0, col: 0 },
+            colored: false,
         }
     }
 
-    fn cat_impl<R: BufRead>(&mut self, mut file: R) -> Result<(), io::Error> {
-        let mut alt_buf; // when there's an escape, allocate a buffer for skipped escapes
-        let mut remaining: Vec<u8> = Vec::new();
-        let mut prev_color: C::Color = Default::default();
-        let mut process_graphemes = |s: &str| {
-            for gr in s.graphemes(true) {
-                let state = self.colorizer.update_state(gr);
-                let color = C::calculate_color(state, &self.flag);
-                if color != prev_color {
-                    self.writer.write_fmt(format_args!("{color}"))?;
-                    prev_color = color;
+    pub(crate) fn set_granularity(&mut self, granularity: Granularity) {
+        self.units = Units::new(granularity);
+    }
+
+    #[cfg(feature = "rayon")]
+    pub(crate) const fn granularity(&self) -> Granularity {
+        self.units.granularity
+    }
+
+    /// The parser, which is in the escape sequence that the last chunk ended in, if there is one
+    #[cfg(feature = "rayon")]
+    pub(crate) const fn parser(&self) -> &Parser {
+        &self.parser
+    }
+
+    pub(crate) const fn colorizer(&self) -> &C {
+        &self.colorizer
+    }
+
+    pub(crate) fn colorizer_mut(&mut self) -> &mut C {
+        &mut self.colorizer
+    }
+
+    /// A painter that continues on `line`, right after a line break that `self` colorized.
+    ///
+    /// When units are bigger than graphemes, the line has to start with a new unit. The color that the terminal has
+    /// before it isn't known, so the painter always writes the color of its first unit, and
+    /// [`Painter::repeated_escape`] finds out whether it was already written.
+    #[cfg(feature = "rayon")]
+    pub(crate) fn fork_at_line(&self, line: u32) -> Self
+    where
+        C: Clone,
+    {
+        let mut colorizer = self.colorizer.clone();
+        colorizer.seek(Position { line, col: 0 });
+        let mut units = Units::new(self.units.granularity);
+        let colored = units.granularity == Granularity::Grapheme;
+        if colored {
+            // the line break was written with the color of the start of the line
+            units.color = Some(colorizer.calculate_color(colorizer.state(), &self.flag));
+        }
+        Self {
+            colorizer,
+            flag: self.flag.clone(),
+            escape_mode: self.escape_mode,
+            invalid_utf8: self.invalid_utf8,
+            reset: self.reset,
+            units,
+            parser: Parser::new(),
+            remaining: Partial::EMPTY,
+            carry: String::new(),
+            carry_written: 0,
+            carry_start: Position { line: 0, col: 0 },
+            colored,
+        }
+    }
+
+    /// The length of the escape that a painter forked at `line` starts with, when `self` stopped with the same color
+    /// on the terminal, and `0` otherwise. `first` is the first grapheme of the line, which starts a new unit.
+    #[cfg(feature = "rayon")]
+    pub(crate) fn repeated_escape(&self, line: u32, first: &str) -> usize
+    where
+        C: Clone,
+    {
+        if self.units.granularity == Granularity::Grapheme || !self.colored {
+            return 0;
+        }
+        let mut colorizer = self.colorizer.clone();
+        colorizer.seek(Position { line, col: 0 });
+        let state = colorizer.update_state(first);
+        let color = colorizer.calculate_color(state, &self.flag);
+        if self.units.color.as_ref() != Some(&color) {
+            return 0;
+        }
+        let mut escape = Vec::new();
+        // writing to a `Vec` can't fail
+        let _ = colorizer.write_color(&color, &mut escape);
+        escape.len()
+    }
+
+    fn process_grapheme<W: Write>(&mut self, gr: &str, writer: &mut W) -> Result<(), io::Error> {
+        let state = self.colorizer.update_state(gr);
+        if self.units.starts(gr) || self.units.color.is_none() {
+            let color = Some(self.colorizer.calculate_color(state, &self.flag));
+            // neighboring units with the same color share their escape
+            self.colored &= self.units.color == color;
+            self.units.color = color;
+        }
+        // the color of a unit has to be written again after a reset
+        if let (false, Some(color)) = (self.colored, &self.units.color) {
+            self.colorizer.write_color(color, writer)?;
+            self.colored = true;
+        }
+        writer.write_all(gr.as_bytes())
+    }
+
+    /// Moves the colorizer over all of `grapheme` when only its start was colorized, since a continuation can move
+    /// the cursor differently, like `\n` after `\r`
+    fn continue_carry(&mut self, grapheme: &str) {
+        self.colorizer.seek(self.carry_start);
+        self.colorizer.update_state(grapheme);
+    }
+
+    /// Writes the first `end` bytes of the carry, which are a whole grapheme, and clears the carry
+    fn write_carry<W: Write>(&mut self, end: usize, writer: &mut W) -> Result<(), io::Error> {
+        let carry = std::mem::take(&mut self.carry);
+        let res = if self.carry_written == 0 {
+            self.process_grapheme(&carry[..end], writer)
+        } else {
+            // the start of the grapheme was already colorized by `flush`, so the rest continues it
+            self.continue_carry(&carry[..end]);
+            writer.write_all(&carry.as_bytes()[self.carry_written..end])
+        };
+        self.carry_written = 0;
+        // keep the allocation around
+        self.carry = carry;
+        self.carry.clear();
+        res
+    }
+
+    /// Writes the carried grapheme, if there is one
+    fn flush_carry<W: Write>(&mut self, writer: &mut W) -> Result<(), io::Error> {
+        if self.carry.is_empty() {
+            return Ok(());
+        }
+        self.write_carry(self.carry.len(), writer)
+    }
+
+    /// Colorizes `s`, which has to end at a grapheme boundary.
+    ///
+    /// Two ascii characters in a row are always separate graphemes, except for `\r\n`, so ascii is colorized a byte
+    /// at a time. Only the spans around non-ascii characters are segmented.
+    fn process_graphemes<W: Write>(&mut self, s: &str, writer: &mut W) -> Result<(), io::Error> {
+        let bytes = s.as_bytes();
+        let mut i = 0;
+        while i < bytes.len() {
+            // the last ascii character before non-ascii can be combined with it, so it's left to the slow path
+            let run = bytes[i..].iter().position(|b| !b.is_ascii());
+            let ascii_end = run.map_or(bytes.len(), |run| i + run.saturating_sub(1));
+            while i < ascii_end {
+                let len = if bytes[i..].starts_with(b"\r\n") {
+                    2
+                } else {
+                    1
+                };
+                self.process_grapheme(&s[i..i + len], writer)?;
+                i += len;
+            }
+            if i == bytes.len() {
+                break;
+            }
+
+            // segment up to the next boundary between two ascii characters
+            let mut end = i + 1;
+            while end < bytes.len()
+                && !(bytes[end - 1].is_ascii()
+                    && bytes[end].is_ascii()
+                    && &bytes[end - 1..=end] != b"\r\n")
+            {
+                end += 1;
+            }
+            for gr in s[i..end].graphemes(true) {
+                self.process_grapheme(gr, writer)?;
+            }
+            i = end;
+        }
+        Ok(())
+    }
+
+    fn process_str<W: Write>(&mut self, mut s: &str, writer: &mut W) -> Result<(), io::Error> {
+        if !self.carry.is_empty() {
+            // join the carried grapheme with the start of `s` until there is a boundary after it.
+            // boundaries only depend on what comes before them, so `s` can be segmented on its own after that.
+            let carried = self.carry.len();
+            let mut taken = 0;
+            loop {
+                let Some(gr) = s[taken..].graphemes(true).next() else {
+                    return Ok(());
+                };
+                self.carry.push_str(gr);
+                taken += gr.len();
+
+                let first = self.carry.graphemes(true).next().unwrap_or_default().len();
+                if first < self.carry.len() {
+                    self.write_carry(first, writer)?;
+                    s = &s[first - carried..];
+                    break;
                 }
-                self.writer.write_all(gr.as_bytes())?;
             }
-            Ok::<(), std::io::Error>(())
+        }
+
+        let Some((last_start, last)) = s.grapheme_indices(true).next_back() else {
+            return Ok(());
         };
-        loop {
-            let buf = file.fill_buf()?;
-            let buf_len = buf.len();
-            if buf.is_empty() {
-                break;
+        self.process_graphemes(&s[..last_start], writer)?;
+        // nothing continues a line break or tab, so there's no need to wait for the next chunk
+        if matches!(last.as_bytes(), b"\n" | b"\r\n" | b"\t") {
+            self.process_grapheme(last, writer)
+        } else {
+            self.carry.push_str(last);
+            Ok(())
+        }
+    }
+
+    /// Handles an invalid utf-8 sequence according to `self.invalid_utf8`
+    fn process_invalid<W: Write>(&mut self, bytes: &[u8], writer: &mut W) -> Result<(), io::Error> {
+        match self.invalid_utf8 {
+            InvalidUtf8::Replace => self.process_str("\u{fffd}", writer),
+            InvalidUtf8::Raw => {
+                self.flush_carry(writer)?;
+                writer.write_all(bytes)
             }
+            InvalidUtf8::Error => Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                InvalidUtf8Error {
+                    bytes: bytes.to_vec(),
+                },
+            )),
+        }
+    }
 
-            // only use the escape skipper if there is an escape
-            // benchmarks suggest that checking for an escape has about a 15% performance increase when there is no escape
-            let buf = if memchr::memchr(0x1b, buf).is_some() {
-                alt_buf = Vec::with_capacity(buf.len());
-                alt_buf.extend(EscapeSkipper::new(buf.iter().copied()));
-                &alt_buf
-            } else {
-                buf
+    /// Handles an incomplete code point that can't be completed anymore
+    fn flush_remaining<W: Write>(&mut self, writer: &mut W) -> Result<(), io::Error> {
+        if self.remaining.is_empty() {
+            return Ok(());
+        }
+        let remaining = std::mem::replace(&mut self.remaining, Partial::EMPTY);
+        self.process_invalid(remaining.as_bytes(), writer)
+    }
+
+    /// Processes text that does not contain escapes, keeping an incomplete utf-8 sequence at the end in `self.remaining`
+    fn process_text<W: Write>(&mut self, mut buf: &[u8], writer: &mut W) -> Result<(), io::Error> {
+        if !self.remaining.is_empty() {
+            // finish the code point in `remaining` first
+            let carried = self.remaining.len;
+            let take = buf.len().min(4 - carried);
+            let mut joined = self.remaining.bytes;
+            joined[carried..carried + take].copy_from_slice(&buf[..take]);
+            let joined = &joined[..carried + take];
+            self.remaining = Partial::EMPTY;
+            let used = match std::str::from_utf8(joined) {
+                Ok(str) => self.process_str(str, writer).map(|()| joined.len()),
+                Err(e) if e.valid_up_to() != 0 => {
+                    let str = std::str::from_utf8(&joined[..e.valid_up_to()]).unwrap();
+                    self.process_str(str, writer).map(|()| e.valid_up_to())
+                }
+                // still incomplete, so all of `buf` was taken
+                Err(e) if e.error_len().is_none() => {
+                    self.remaining = Partial::new(joined);
+                    return Ok(());
+                }
+                Err(e) => {
+                    let len = e.error_len().unwrap_or_default();
+                    self.process_invalid(&joined[..len], writer).map(|()| len)
+                }
             };
+            buf = &buf[used? - carried..];
+        }
 
-            if remaining.is_empty() {
-                match std::str::from_utf8(buf) {
-                    Ok(str) => process_graphemes(str)?,
-                    Err(e) => {
-                        let (s, tail) = buf.split_at(e.valid_up_to());
-                        process_graphemes(std::str::from_utf8(s).unwrap())?;
-                        remaining.extend_from_slice(tail);
-                    }
+        loop {
+            match std::str::from_utf8(buf) {
+                Ok(str) => return self.process_str(str, writer),
+                Err(e) => {
+                    let (s, tail) = buf.split_at(e.valid_up_to());
+                    self.process_str(std::str::from_utf8(s).unwrap(), writer)?;
+                    let Some(len) = e.error_len() else {
+                        self.remaining = Partial::new(tail);
+                        return Ok(());
+                    };
+                    self.process_invalid(&tail[..len], writer)?;
+                    buf = &tail[len..];
                 }
-            } else {
-                remaining.extend_from_slice(buf);
-                match std::str::from_utf8(&remaining) {
-                    Ok(str) => {
-                        process_graphemes(str)?;
-                        remaining.clear();
-                    }
-                    Err(e) => {
-                        let (s, rem) = remaining.split_at(e.valid_up_to());
-                        let str = std::str::from_utf8(s).unwrap();
-                        process_graphemes(str)?;
-                        // avoid borrow issue
-                        let mut new_remaining = Vec::with_capacity(rem.len());
-                        new_remaining.extend_from_slice(rem);
-                        remaining = new_remaining;
+            }
+        }
+    }
+
+    /// Writes what is kept of the text before an escape, since neither code points nor graphemes can continue after one
+    fn end_text<W: Write>(&mut self, writer: &mut W) -> Result<(), io::Error> {
+        self.flush_remaining(writer)?;
+        self.flush_carry(writer)
+    }
+
+    /// Handles a complete escape sequence according to `self.escape_mode`
+    fn process_escape<W: Write>(
+        &mut self,
+        sequence: Sequence<'_>,
+        writer: &mut W,
+    ) -> Result<(), io::Error> {
+        self.end_text(writer)?;
+        match self.escape_mode {
+            EscapeMode::Strip => Ok(()),
+            EscapeMode::Passthrough => match sequence.sgr_params() {
+                Some(params) => {
+                    // a reset also resets our color, so it has to be written again
+                    if write_sgr_without_foreground(writer, params)? {
+                        self.colored = false;
                     }
+                    Ok(())
                 }
-            }
-            file.consume(buf_len);
+                None => writer.write_all(sequence.as_bytes()),
+            },
         }
+    }
 
+    /// Colorizes `buf` and writes it to `writer`
+    pub(crate) fn write<W: Write>(&mut self, buf: &[u8], writer: &mut W) -> Result<(), io::Error> {
+        // stripped sequences are never written, so they don't need to be kept while they arrive
+        let limit = match self.escape_mode {
+            EscapeMode::Strip => 0,
+            EscapeMode::Passthrough => DEFAULT_SEQUENCE_LIMIT,
+        };
+        let mut parser = std::mem::take(&mut self.parser).with_limit(limit);
+        let res = parser.parse(buf, |event| match event {
+            Event::Text(text) => self.process_text(text, writer),
+            Event::Sequence(sequence) => self.process_escape(sequence, writer),
+            // a sequence that is too long to be kept is dropped in every mode
+            Event::Dropped(_) => self.end_text(writer),
+        });
+        self.parser = parser;
+        res
+    }
+
+    /// Writes the carried grapheme and resets the colors of the terminal if a color was written and resetting is on
+    pub(crate) fn flush<W: Write>(&mut self, writer: &mut W) -> Result<(), io::Error> {
+        // the carried grapheme is kept, so that its continuation in the next chunk isn't colorized on its own
+        if self.carry.len() > self.carry_written {
+            let carry = std::mem::take(&mut self.carry);
+            let res = if self.carry_written == 0 {
+                self.carry_start = self.colorizer.position();
+                self.process_grapheme(&carry, writer)
+            } else {
+                self.continue_carry(&carry);
+                writer.write_all(&carry.as_bytes()[self.carry_written..])
+            };
+            self.carry_written = carry.len();
+            self.carry = carry;
+            res?;
+        }
+        if self.reset && std::mem::take(&mut self.colored) {
+            writer.write_fmt(format_args!("{}", C::Resetter::default()))?;
+        }
         Ok(())
     }
+
+    /// Resets the colors of the terminal if resetting is on, even if no color was written
+    pub(crate) fn write_reset<W: Write>(&mut self, writer: &mut W) -> Result<(), io::Error> {
+        if !self.reset {
+            return Ok(());
+        }
+        self.colored = false;
+        writer.write_fmt(format_args!("{}", C::Resetter::default()))
+    }
+
+    /// Ends the input. Everything that is unfinished is written or discarded.
+    pub(crate) fn finish<W: Write>(&mut self, writer: &mut W) -> Result<(), io::Error> {
+        // an unfinished escape is dropped, since it might swallow the reset
+        self.parser.reset();
+        self.flush_remaining(writer)?;
+        self.flush_carry(writer)
+    }
+}
+
+/// The main driver struct
+///
+/// The colorizer keeps its position between calls to [`QueerCat::cat`], so a stream can be colorized in pieces.
+/// Its state can be saved by cloning [`QueerCat::colorizer`] and restored or moved with [`QueerCat::colorizer_mut`].
+///
+/// # Examples
+/// ```
+/// use queercat_lib::{transgender, Bits24, Colorizer, Position, QueerCat, QueerCatFrequency};
+/// let mut whole = QueerCat::new(Bits24::new(QueerCatFrequency::Original), Vec::new(), transgender());
+/// whole.cat(&b"first page\nsecond page\n"[..])?;
+///
+/// let mut first = QueerCat::new(Bits24::new(QueerCatFrequency::Original), Vec::new(), transgender())
+///     .with_reset(false);
+/// first.cat(&b"first page\n"[..])?;
+/// // the second page is colorized on its own, starting where the first page ended
+/// let mut second = QueerCat::new(Bits24::new(QueerCatFrequency::Original), Vec::new(), transgender());
+/// second.colorizer_mut().seek(Position { line: 1, col: 0 });
+/// second.cat(&b"second page\n"[..])?;
+///
+/// let mut pieces = first.into_inner();
+/// pieces.extend(second.into_inner());
+/// assert_eq!(pieces, whole.into_inner());
+/// # Ok::<(), std::io::Error>(())
+/// ```
+pub struct QueerCat<'a, W: Write, C: Colorizer> {
+    painter: Painter<'a, C>,
+    writer: W,
+}
+
+impl<'a, W: Write, C: Colorizer> QueerCat<'a, W, C> {
+    #[must_use]
+    pub const fn new(colorizer: C, writer: W, flag: Flag<'a>) -> Self {
+        Self {
+            painter: Painter::new(colorizer, flag),
+            writer,
+        }
+    }
+
+    painter_builders! {
+        /// Sets whether the colors of the terminal are reset after each call to [`QueerCat::cat`]. Defaults to `true`.
+        /// Without the reset, the last color stays active until the next call.
+    }
+
+    /// Returns the inner writer
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+
+    fn cat_impl<R: BufRead>(&mut self, mut file: R) -> Result<(), io::Error> {
+        loop {
+            let buf = file.fill_buf()?;
+            let buf_len = buf.len();
+            if buf.is_empty() {
+                break;
+            }
+            self.painter.write(buf, &mut self.writer)?;
+            file.consume(buf_len);
+        }
+        self.painter.finish(&mut self.writer)
+    }
     /// Colorizes input from `file` and writes it to the `writer`.
     /// # Errors
     /// Returns `Err` when writing with `self.writer` or reading `file` fails
     pub fn cat<R: BufRead>(&mut self, file: R) -> Result<(), io::Error> {
         let res = self.cat_impl(file);
-        self.writer
-            .write_fmt(format_args!("{}", C::Resetter::default()))?;
+        self.painter.write_reset(&mut self.writer)?;
         res
     }
 }
 
-use std::iter::Iterator;
-struct EscapeSkipper<I>(I);
-impl<I> EscapeSkipper<I> {
-    const fn new(item: I) -> Self {
-        Self(item)
+/// Writes the SGR sequence with the parameters `params` without any of its foreground color parameters.
+/// Nothing is written if only foreground colors were set.
+/// Returns whether the sequence resets all attributes.
+fn write_sgr_without_foreground<W: Write>(writer: &mut W, params: &[u8]) -> io::Result<bool> {
+    let mut params = params.split(|&b| b == b';');
+    let mut written = false;
+    let mut reset = false;
+    let mut write_param = |writer: &mut W, param: &[u8]| {
+        writer.write_all(if written { b";" } else { b"\x1b[" })?;
+        written = true;
+        writer.write_all(param)
+    };
+
+    while let Some(param) = params.next() {
+        // colon separated sub-parameters belong to their parameter, so only the first number matters
+        let code = param.split(|&b| b == b':').next().unwrap_or_default();
+        let has_subparams = code.len() != param.len();
+        let code = code.iter().try_fold(0u32, |acc, b| {
+            acc.checked_mul(10)?.checked_add(u32::from(b - b'0'))
+        });
+
+        match code {
+            Some(0) => {
+                reset = true;
+                write_param(writer, param)?;
+            }
+            Some(30..=37 | 39 | 90..=97) => {}
+            // extended colors. `38;5;n` and `38;2;r;g;b` take more parameters
+            Some(code @ (38 | 48 | 58)) => {
+                let mut args: [&[u8]; 4] = [&[]; 4];
+                let mut arg_count = 0;
+                if !has_subparams {
+                    let extra = match params.next() {
+                        Some(mode) => {
+                            args[0] = mode;
+                            arg_count = 1;
+                            match mode {
+                                b"5" => 1,
+                                b"2" => 3,
+                                _ => 0,
+                            }
+                        }
+                        None => 0,
+                    };
+                    for arg in params.by_ref().take(extra) {
+                        args[arg_count] = arg;
+                        arg_count += 1;
+                    }
+                }
+                if code != 38 {
+                    write_param(writer, param)?;
+                    for arg in &args[..arg_count] {
+                        write_param(writer, arg)?;
+                    }
+                }
+            }
+            _ => write_param(writer, param)?,
+        }
+    }
+
+    if written {
+        writer.write_all(b"m")?;
     }
+    Ok(reset)
 }
 
-impl<I: Iterator<Item = u8>> Iterator for EscapeSkipper<I> {
-    type Item = u8;
-    fn next(&mut self) -> Option<Self::Item> {
-        let item = self.0.next()?;
-        if item != 0x1b {
-            return Some(item);
-        }
-        loop {
-            let i = self.0.next()?;
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-            if i.is_ascii_alphabetic() {
-                return self.next(); // recursively skip escapes. this will not stack overflow because of tail-call optimization
-            }
+    /// The sequence that is written for the parameters `params`, and whether it resets
+    fn filter(params: &str) -> (String, bool) {
+        let mut output = Vec::new();
+        let reset = write_sgr_without_foreground(&mut output, params.as_bytes()).unwrap();
+        (String::from_utf8(output).unwrap(), reset)
+    }
+
+    #[test]
+    fn foreground_colors_are_removed() {
+        for params in [
+            "31",
+            "39",
+            "91",
+            "38;5;12",
+            "38;2;1;2;3",
+            "38:5:12",
+            "38:2::1:2:3",
+        ] {
+            assert_eq!(filter(params), (String::new(), false), "{params}");
         }
     }
+
+    #[test]
+    fn other_attributes_are_kept() {
+        assert_eq!(filter("1;4"), ("\x1b[1;4m".to_owned(), false));
+        assert_eq!(filter("1;38;5;12;4"), ("\x1b[1;4m".to_owned(), false));
+        assert_eq!(filter("38;2;1;2;3;1"), ("\x1b[1m".to_owned(), false));
+        assert_eq!(filter("1;39;42"), ("\x1b[1;42m".to_owned(), false));
+        assert_eq!(filter("38:2::1:2:3;4"), ("\x1b[4m".to_owned(), false));
+        // background and underline colors take the same arguments as foreground colors
+        assert_eq!(filter("48;5;12;31"), ("\x1b[48;5;12m".to_owned(), false));
+        assert_eq!(
+            filter("38;5;1;48;2;4;5;6"),
+            ("\x1b[48;2;4;5;6m".to_owned(), false)
+        );
+        assert_eq!(
+            filter("58:2::1:2:3;39"),
+            ("\x1b[58:2::1:2:3m".to_owned(), false)
+        );
+    }
+
+    #[test]
+    fn resets_are_kept_and_reported() {
+        assert_eq!(filter(""), ("\x1b[m".to_owned(), true));
+        assert_eq!(filter("0"), ("\x1b[0m".to_owned(), true));
+        assert_eq!(filter("0;38;5;12"), ("\x1b[0m".to_owned(), true));
+        assert_eq!(filter("38;2;1;2;3;0;1"), ("\x1b[0;1m".to_owned(), true));
+        // `0` as an argument of a color is not a reset
+        assert_eq!(filter("38;5;0"), (String::new(), false));
+        assert_eq!(filter("38;2;0;0;0;1"), ("\x1b[1m".to_owned(), false));
+    }
 }
@@ -0,0 +1,565 @@
+#![allow(clippy::must_use_candidate)]
+//! A streaming parser for ANSI/VT escape sequences.
+//!
+//! The parser follows ECMA-48 and the DEC/xterm extensions to it. It is fed arbitrary chunks of bytes and keeps
+//! unfinished sequences between calls, so sequences that are split across buffers are still recognized.
+
+/// How many bytes of an escape sequence a [`Parser`] keeps by default. This is enough for hyperlinks, window titles
+/// and clipboard contents, while a string that is never terminated can't take up all of the memory.
+pub const DEFAULT_SEQUENCE_LIMIT: usize = 1 << 20;
+
+/// The kinds of escape sequences that the [`Parser`] recognizes
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SequenceKind {
+    /// A Control Sequence (`ESC [ ...`), like SGR or cursor movement
+    Csi,
+    /// An Operating System Command (`ESC ] ...`), like window titles or hyperlinks
+    Osc,
+    /// A Device Control String (`ESC P ...`)
+    Dcs,
+    /// A Start Of String (`ESC X ...`)
+    Sos,
+    /// A Privacy Message (`ESC ^ ...`)
+    Pm,
+    /// An Application Program Command (`ESC _ ...`)
+    Apc,
+    /// An escape with intermediate bytes (`ESC 0x20..=0x2f ... 0x30..=0x7e`), like charset designators (`ESC ( B`)
+    Nf,
+    /// A private two-byte escape (`ESC 0x30..=0x3f`), like saving the cursor (`ESC 7`)
+    Fp,
+    /// A C1 control function as a two-byte escape (`ESC 0x40..=0x5f`), like reverse index (`ESC M`)
+    Fe,
+    /// A standardized two-byte escape (`ESC 0x60..=0x7e`), like a full reset (`ESC c`)
+    Fs,
+}
+
+impl SequenceKind {
+    /// Whether the sequence is a control string that only ends with a string terminator
+    pub const fn is_string(self) -> bool {
+        matches!(
+            self,
+            Self::Osc | Self::Dcs | Self::Sos | Self::Pm | Self::Apc
+        )
+    }
+}
+
+/// A complete escape sequence
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Sequence<'a> {
+    kind: SequenceKind,
+    bytes: &'a [u8],
+}
+
+impl<'a> Sequence<'a> {
+    /// The kind of the sequence
+    pub const fn kind(&self) -> SequenceKind {
+        self.kind
+    }
+
+    /// The raw bytes of the sequence, starting with `ESC` and including the terminator, if there is one
+    pub const fn as_bytes(&self) -> &'a [u8] {
+        self.bytes
+    }
+
+    /// The parameter bytes of a Control Sequence, including private markers like `?`
+    pub fn csi_params(&self) -> Option<&'a [u8]> {
+        if self.kind != SequenceKind::Csi {
+            return None;
+        }
+        let body = &self.bytes[2..self.bytes.len() - 1];
+        let end = body
+            .iter()
+            .position(|b| !(0x30..=0x3f).contains(b))
+            .unwrap_or(body.len());
+        Some(&body[..end])
+    }
+
+    /// The final byte of a Control Sequence
+    pub fn csi_final(&self) -> Option<u8> {
+        (self.kind == SequenceKind::Csi).then(|| self.bytes[self.bytes.len() - 1])
+    }
+
+    /// The parameters of a Select Graphic Rendition sequence (`ESC [ params m`) without private markers or
+    /// intermediate bytes. Returns `None` for every other sequence.
+    pub fn sgr_params(&self) -> Option<&'a [u8]> {
+        let params = self.csi_params()?;
+        let is_sgr = self.csi_final() == Some(b'm')
+            // intermediate bytes sit between the parameters and the final byte
+            && params.len() + 3 == self.bytes.len()
+            && params.iter().all(|b| b.is_ascii_digit() || *b == b';' || *b == b':');
+        is_sgr.then_some(params)
+    }
+}
+
+/// A piece of parsed input
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Event<'a> {
+    /// Bytes that are not part of an escape sequence
+    Text(&'a [u8]),
+    /// A complete escape sequence
+    Sequence(Sequence<'a>),
+    /// A complete escape sequence that was longer than the limit of the parser. Its bytes were dropped as they arrived.
+    Dropped(SequenceKind),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum State {
+    Ground,
+    /// After `ESC`
+    Escape,
+    /// After `ESC` and at least one intermediate byte
+    EscapeIntermediate,
+    /// Inside of a Control Sequence
+    Csi,
+    /// Inside of a control string
+    String(SequenceKind),
+    /// After `ESC` inside of a control string. `ESC \` terminates the string.
+    StringEscape(SequenceKind),
+}
+
+const ESC: u8 = 0x1b;
+const BEL: u8 = 0x07;
+const CAN: u8 = 0x18;
+const SUB: u8 = 0x1a;
+
+/// What happens with the byte that is fed to the state machine
+enum Step {
+    /// The byte is part of the current sequence
+    Continue,
+    /// The byte is a control function that is executed in the middle of the sequence, so it is passed on as text and
+    /// the sequence continues after it
+    Execute,
+    /// The byte completes the current sequence
+    Complete(SequenceKind),
+    /// The byte starts a new escape sequence, so the string that was in progress ends before the `ESC` that came before it
+    Terminate(SequenceKind),
+    /// The current sequence is invalid. The byte is handled again in the ground state.
+    Abort,
+    /// The current sequence and the byte are discarded
+    Cancel,
+    /// The current sequence is discarded and the byte starts a new one
+    Restart,
+}
+
+/// A streaming escape sequence parser that survives chunk boundaries
+#[derive(Debug, Clone)]
+pub struct Parser {
+    state: State,
+    /// The bytes of the sequence in progress
+    sequence: Vec<u8>,
+    /// How many bytes of a sequence are kept
+    limit: usize,
+    /// Whether the sequence in progress is longer than `limit`, so its bytes are dropped
+    dropping: bool,
+}
+
+impl Default for Parser {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Parser {
+    /// Creates a parser in the ground state
+    pub const fn new() -> Self {
+        Self {
+            state: State::Ground,
+            sequence: Vec::new(),
+            limit: DEFAULT_SEQUENCE_LIMIT,
+            dropping: false,
+        }
+    }
+
+    /// Sets how many bytes of an escape sequence are kept. Defaults to [`DEFAULT_SEQUENCE_LIMIT`].
+    /// Longer sequences are dropped as they arrive and reported as [`Event::Dropped`], so with a limit of 0, no
+    /// sequence is ever buffered.
+    #[must_use]
+    pub const fn with_limit(mut self, limit: usize) -> Self {
+        self.limit = limit;
+        self
+    }
+
+    /// Whether the parser is inside of an unfinished escape sequence
+    pub fn in_sequence(&self) -> bool {
+        self.state != State::Ground
+    }
+
+    /// Discards an unfinished escape sequence, if there is one
+    pub fn reset(&mut self) {
+        self.state = State::Ground;
+        self.sequence.clear();
+        self.dropping = false;
+    }
+
+    /// Starts a new sequence with `ESC`
+    fn start(&mut self) {
+        self.reset();
+        self.state = State::Escape;
+        self.push(ESC);
+    }
+
+    /// Adds `byte` to the sequence in progress, unless the sequence is too long to be kept
+    fn push(&mut self, byte: u8) {
+        if self.dropping {
+            return;
+        }
+        if self.sequence.len() >= self.limit {
+            self.dropping = true;
+            self.sequence = Vec::new();
+            return;
+        }
+        self.sequence.push(byte);
+    }
+
+    fn step(&self, byte: u8) -> Step {
+        use SequenceKind::{Csi, Fe, Fp, Fs, Nf, Osc};
+        match self.state {
+            State::Ground => unreachable!("the ground state is handled by `Parser::parse`"),
+            _ if byte == CAN || byte == SUB => Step::Cancel,
+            State::Escape => match byte {
+                // introducers of control sequences and strings, and intermediate bytes
+                b'[' | b']' | b'P' | b'X' | b'^' | b'_' | 0x20..=0x2f => Step::Continue,
+                0x30..=0x3f => Step::Complete(Fp),
+                0x40..=0x5f => Step::Complete(Fe),
+                0x60..=0x7e => Step::Complete(Fs),
+                ESC => Step::Restart,
+                // terminals execute other control functions without ending the sequence, and ignore `DEL`
+                0x00..=0x1f => Step::Execute,
+                0x7f => Step::Continue,
+                _ => Step::Abort,
+            },
+            State::EscapeIntermediate => match byte {
+                0x30..=0x7e => Step::Complete(Nf),
+                ESC => Step::Restart,
+                0x00..=0x1f => Step::Execute,
+                0x20..=0x2f | 0x7f => Step::Continue,
+                _ => Step::Abort,
+            },
+            State::Csi => match byte {
+                0x40..=0x7e => Step::Complete(Csi),
+                ESC => Step::Restart,
+                0x00..=0x1f => Step::Execute,
+                0x20..=0x3f | 0x7f => Step::Continue,
+                _ => Step::Abort,
+            },
+            State::String(kind) => match byte {
+                BEL if kind == Osc => Step::Complete(kind),
+                _ => Step::Continue,
+            },
+            State::StringEscape(kind) => match byte {
+                b'\\' => Step::Complete(kind),
+                _ => Step::Terminate(kind),
+            },
+        }
+    }
+
+    /// Moves to the state after `byte` when the current sequence continues
+    fn advance(&mut self, byte: u8) {
+        use SequenceKind::{Apc, Dcs, Osc, Pm, Sos};
+        self.state = match (self.state, byte) {
+            (State::Escape, b'[') => State::Csi,
+            (State::Escape, b']') => State::String(Osc),
+            (State::Escape, b'P') => State::String(Dcs),
+            (State::Escape, b'X') => State::String(Sos),
+            (State::Escape, b'^') => State::String(Pm),
+            (State::Escape, b'_') => State::String(Apc),
+            (State::Escape, 0x20..=0x2f) => State::EscapeIntermediate,
+            (State::String(kind), ESC) => State::StringEscape(kind),
+            (state, _) => state,
+        };
+        self.push(byte);
+    }
+
+    /// Reports the sequence in progress as complete and returns to the ground state
+    fn complete<E>(
+        &mut self,
+        kind: SequenceKind,
+        f: &mut impl FnMut(Event<'_>) -> Result<(), E>,
+    ) -> Result<(), E> {
+        self.state = State::Ground;
+        if self.dropping {
+            self.dropping = false;
+            f(Event::Dropped(kind))
+        } else {
+            f(Event::Sequence(Sequence {
+                kind,
+                bytes: &self.sequence,
+            }))
+        }
+    }
+
+    /// Parses `input`, calling `f` with every piece of text and every complete escape sequence in order.
+    /// A sequence that is unfinished at the end of `input` is kept and continued by the next call.
+    /// # Errors
+    /// Stops parsing and returns the error when `f` returns an error
+    pub fn parse<E>(
+        &mut self,
+        input: &[u8],
+        mut f: impl FnMut(Event<'_>) -> Result<(), E>,
+    ) -> Result<(), E> {
+        let mut i = 0;
+        while i < input.len() {
+            if self.state == State::Ground {
+                let rest = &input[i..];
+                let Some(start) = memchr::memchr(ESC, rest) else {
+                    return f(Event::Text(rest));
+                };
+                if start != 0 {
+                    f(Event::Text(&rest[..start]))?;
+                }
+                self.start();
+                i += start + 1;
+                continue;
+            }
+
+            let byte = input[i];
+            match self.step(byte) {
+                Step::Continue => {
+                    self.advance(byte);
+                    i += 1;
+                }
+                Step::Execute => {
+                    f(Event::Text(&input[i..=i]))?;
+                    i += 1;
+                }
+                Step::Complete(kind) => {
+                    self.push(byte);
+                    self.complete(kind, &mut f)?;
+                    i += 1;
+                }
+                Step::Terminate(kind) => {
+                    // the `ESC` belongs to the next sequence
+                    if !self.dropping {
+                        self.sequence.pop();
+                    }
+                    self.complete(kind, &mut f)?;
+                    self.start();
+                }
+                Step::Abort => self.reset(),
+                Step::Cancel => {
+                    self.reset();
+                    i += 1;
+                }
+                Step::Restart => {
+                    self.start();
+                    i += 1;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// An owned [`Event`], with neighboring text joined
+    #[derive(Debug, PartialEq, Eq)]
+    enum Parsed {
+        Text(Vec<u8>),
+        Sequence(SequenceKind, Vec<u8>),
+        Dropped(SequenceKind),
+    }
+
+    fn parse_chunks<'a>(
+        mut parser: Parser,
+        chunks: impl IntoIterator<Item = &'a [u8]>,
+    ) -> Vec<Parsed> {
+        let mut parsed = Vec::new();
+        for chunk in chunks {
+            parser
+                .parse(chunk, |event| {
+                    match (event, parsed.last_mut()) {
+                        (Event::Text(text), Some(Parsed::Text(last))) => {
+                            last.extend_from_slice(text);
+                        }
+                        (Event::Text(text), _) => parsed.push(Parsed::Text(text.to_vec())),
+                        (Event::Sequence(sequence), _) => parsed.push(Parsed::Sequence(
+                            sequence.kind(),
+                            sequence.as_bytes().to_vec(),
+                        )),
+                        (Event::Dropped(kind), _) => parsed.push(Parsed::Dropped(kind)),
+                    }
+                    Ok::<(), ()>(())
+                })
+                .unwrap();
+        }
+        parsed
+    }
+
+    fn parse(input: &[u8]) -> Vec<Parsed> {
+        parse_chunks(Parser::new(), [input])
+    }
+
+    fn text(text: &[u8]) -> Parsed {
+        Parsed::Text(text.to_vec())
+    }
+
+    fn sequence(kind: SequenceKind, bytes: &[u8]) -> Parsed {
+        Parsed::Sequence(kind, bytes.to_vec())
+    }
+
+    /// Parses `input` as a single sequence
+    fn parse_sequence(input: &[u8]) -> Sequence<'_> {
+        let mut parser = Parser::new();
+        let mut kind = None;
+        parser
+            .parse(input, |event| {
+                if let Event::Sequence(sequence) = event {
+                    kind = Some(sequence.kind());
+                }
+                Ok::<(), ()>(())
+            })
+            .unwrap();
+        Sequence {
+            kind: kind.expect("not a sequence"),
+            bytes: input,
+        }
+    }
+
+    #[test]
+    fn sequences_split_at_every_boundary() {
+        let input: &[u8] =
+            b"a\x1b[1;38;5;9mb\x1b]8;;https://example.com\x1b\\link\x1b]0;title\x07\x1b(B\x1b7c\
+            \x1bPq#0\x1b\\\x1b[?25l\xc3\xa9\x1b";
+        let whole = parse(input);
+        for split in 0..=input.len() {
+            let (start, end) = input.split_at(split);
+            assert_eq!(
+                parse_chunks(Parser::new(), [start, end]),
+                whole,
+                "split at {split}"
+            );
+        }
+        assert_eq!(parse_chunks(Parser::new(), input.chunks(1)), whole);
+    }
+
+    #[test]
+    fn osc_ends_with_bel_or_st() {
+        assert_eq!(
+            parse(b"\x1b]0;title\x07a"),
+            [sequence(SequenceKind::Osc, b"\x1b]0;title\x07"), text(b"a")]
+        );
+        assert_eq!(
+            parse(b"\x1b]0;title\x1b\\a"),
+            [
+                sequence(SequenceKind::Osc, b"\x1b]0;title\x1b\\"),
+                text(b"a")
+            ]
+        );
+        // BEL is only a terminator for OSC
+        assert_eq!(
+            parse(b"\x1bPa\x07b\x1b\\"),
+            [sequence(SequenceKind::Dcs, b"\x1bPa\x07b\x1b\\")]
+        );
+        // any other escape ends the string and starts a new sequence
+        assert_eq!(
+            parse(b"\x1b]0;title\x1b[1m"),
+            [
+                sequence(SequenceKind::Osc, b"\x1b]0;title"),
+                sequence(SequenceKind::Csi, b"\x1b[1m")
+            ]
+        );
+    }
+
+    #[test]
+    fn can_and_sub_cancel_sequences() {
+        assert_eq!(parse(b"\x1b[1\x18a"), [text(b"a")]);
+        assert_eq!(parse(b"\x1b]0;title\x1ab"), [text(b"b")]);
+        assert_eq!(parse(b"\x1b(\x18c"), [text(b"c")]);
+        assert_eq!(parse(b"\x1b\x1ad"), [text(b"d")]);
+    }
+
+    #[test]
+    fn two_byte_and_intermediate_escapes_are_classified() {
+        assert_eq!(parse(b"\x1b(B"), [sequence(SequenceKind::Nf, b"\x1b(B")]);
+        assert_eq!(parse(b"\x1b#8"), [sequence(SequenceKind::Nf, b"\x1b#8")]);
+        assert_eq!(parse(b"\x1b7"), [sequence(SequenceKind::Fp, b"\x1b7")]);
+        assert_eq!(parse(b"\x1bM"), [sequence(SequenceKind::Fe, b"\x1bM")]);
+        assert_eq!(parse(b"\x1bc"), [sequence(SequenceKind::Fs, b"\x1bc")]);
+        assert_eq!(
+            parse(b"\x1bX\x1b\\"),
+            [sequence(SequenceKind::Sos, b"\x1bX\x1b\\")]
+        );
+        assert_eq!(
+            parse(b"\x1b^\x1b\\"),
+            [sequence(SequenceKind::Pm, b"\x1b^\x1b\\")]
+        );
+        assert_eq!(
+            parse(b"\x1b_\x1b\\"),
+            [sequence(SequenceKind::Apc, b"\x1b_\x1b\\")]
+        );
+        // a new escape replaces an unfinished one
+        assert_eq!(parse(b"\x1b\x1bc"), [sequence(SequenceKind::Fs, b"\x1bc")]);
+        // bytes outside of ascii are not part of a sequence
+        assert_eq!(parse("\x1bé".as_bytes()), [text("é".as_bytes())]);
+    }
+
+    #[test]
+    fn sgr_params_reject_intermediates_and_private_markers() {
+        assert_eq!(
+            parse_sequence(b"\x1b[1;38:5:9m").sgr_params(),
+            Some(&b"1;38:5:9"[..])
+        );
+        assert_eq!(parse_sequence(b"\x1b[m").sgr_params(), Some(&b""[..]));
+        assert_eq!(parse_sequence(b"\x1b[?1m").sgr_params(), None);
+        assert_eq!(parse_sequence(b"\x1b[>4;2m").sgr_params(), None);
+        assert_eq!(parse_sequence(b"\x1b[1 m").sgr_params(), None);
+        assert_eq!(parse_sequence(b"\x1b[1H").sgr_params(), None);
+        assert_eq!(parse_sequence(b"\x1b[?25l").csi_params(), Some(&b"?25"[..]));
+    }
+
+    #[test]
+    fn c0_controls_inside_of_sequences_are_text() {
+        assert_eq!(
+            parse(b"a\x1b[1\n2m"),
+            [text(b"a\n"), sequence(SequenceKind::Csi, b"\x1b[12m")]
+        );
+        assert_eq!(
+            parse(b"foo\x1b\nbar"),
+            [
+                text(b"foo\n"),
+                sequence(SequenceKind::Fs, b"\x1bb"),
+                text(b"ar")
+            ]
+        );
+        assert_eq!(
+            parse(b"\x1b(\tB"),
+            [text(b"\t"), sequence(SequenceKind::Nf, b"\x1b(B")]
+        );
+        // inside of strings, they are part of the string
+        assert_eq!(
+            parse(b"\x1b]0;a\nb\x07"),
+            [sequence(SequenceKind::Osc, b"\x1b]0;a\nb\x07")]
+        );
+    }
+
+    #[test]
+    fn long_sequences_are_dropped() {
+        let parser = || Parser::new().with_limit(8);
+        assert_eq!(
+            parse_chunks(parser(), [&b"\x1b]0;a long title\x07a\x1b[1m"[..]]),
+            [
+                Parsed::Dropped(SequenceKind::Osc),
+                text(b"a"),
+                sequence(SequenceKind::Csi, b"\x1b[1m")
+            ]
+        );
+        assert_eq!(
+            parse_chunks(parser(), [&b"\x1b]0;a long"[..], b" title\x1b\\", b"\x1bc"]),
+            [
+                Parsed::Dropped(SequenceKind::Osc),
+                sequence(SequenceKind::Fs, b"\x1bc")
+            ]
+        );
+        assert_eq!(
+            parse_chunks(Parser::new().with_limit(0), [&b"\x1b[1ma\x1b]0;\x07"[..]]),
+            [
+                Parsed::Dropped(SequenceKind::Csi),
+                text(b"a"),
+                Parsed::Dropped(SequenceKind::Osc)
+            ]
+        );
+    }
+}
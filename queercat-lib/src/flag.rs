@@ -1,6 +1,8 @@
 #![allow(clippy::unreadable_literal)]
 //! The list of preset flags, and the `Flag` struct
 
+use crate::color::{middle, AnsiColor, Color};
+
 /// The types of color methods
 #[derive(Copy, Clone, Debug)]
 pub enum ColorMethod {
@@ -13,11 +15,59 @@ pub enum ColorMethod {
 pub struct Flag<'a> {
     pub name: &'a str,
     pub ansi_colors: &'a [u8],
+    /// The [basic colors](crate::color::BasicColor) of the flag, used like `ansi_colors`. When it is empty, the
+    /// stripes get the basic colors that look the most like them.
+    pub basic_colors: &'a [u8],
     pub stripe_colors: &'a [crate::color::Color],
     pub factor: crate::Extended,
     pub color_method: ColorMethod,
 }
 
+/// The number of ansi colors that [`Flag::derive_ansi_colors`] picks for rainbows
+pub const RAINBOW_ANSI_COLORS: usize = 30;
+
+impl Flag<'_> {
+    /// Whether the flag is drawn as a rainbow. Flags without any stripes are drawn as a rainbow too, so that every
+    /// flag has colors.
+    pub(crate) fn is_rainbow(&self) -> bool {
+        matches!(self.color_method, ColorMethod::Rainbow) || self.stripe_colors.is_empty()
+    }
+
+    /// Picks the 256 colors that look the most like the stripes of the flag with
+    /// [`AnsiColor::nearest`](crate::color::AnsiColor::nearest), so that flags without `ansi_colors` can be used
+    /// with [`Ansi`](crate::Ansi). Every stripe gets one color, so stripes that are repeated to make them wider stay
+    /// wider. Rainbows and flags without stripes are sampled [`RAINBOW_ANSI_COLORS`] times.
+    ///
+    /// # Examples
+    /// ```
+    /// use queercat_lib::{color::Color, ColorMethod, Extended, Flag};
+    /// let stripes = [Color::from_hex(0xff0000), Color::from_hex(0xff0000), Color::from_hex(0x0000ff)];
+    /// let flag = Flag {
+    ///     name: "custom",
+    ///     ansi_colors: &[],
+    ///     basic_colors: &[],
+    ///     stripe_colors: &stripes,
+    ///     factor: Extended::lit("4.0"),
+    ///     color_method: ColorMethod::Stripes,
+    /// };
+    /// assert_eq!(flag.derive_ansi_colors(), [196, 196, 21]);
+    /// ```
+    #[must_use]
+    pub fn derive_ansi_colors(&self) -> Vec<u8> {
+        if self.is_rainbow() {
+            // the middle of every part of the rainbow
+            (0..RAINBOW_ANSI_COLORS)
+                .map(|i| AnsiColor::nearest(Color::rainbow(middle(i, RAINBOW_ANSI_COLORS))).0)
+                .collect()
+        } else {
+            self.stripe_colors
+                .iter()
+                .map(|&color| AnsiColor::nearest(color).0)
+                .collect()
+        }
+    }
+}
+
 use unstringify::unstringify;
 
 macro_rules! stripe_flag {
@@ -25,6 +75,7 @@ macro_rules! stripe_flag {
         {
         .name = $name:literal,
          .ansii_pattern= {.codes_count = $cc:tt, .ansii_codes={$($ansi:literal),*}},
+         $(.basic_codes = {$($basic:literal),*},)?
         .color_pattern = {
             .stripes_count = $sc:tt,
             .stripes_colors = {
@@ -41,6 +92,7 @@ macro_rules! stripe_flag {
              Flag {
         name: $name,
         ansi_colors: &[$($ansi),*],
+        basic_colors: &[$($($basic),*)?],
         stripe_colors: &STRIPES,
         factor: crate::Extended::lit(stringify!($factor)),
         color_method: ColorMethod::Stripes,
@@ -60,6 +112,7 @@ stripe_flag! {
             .codes_count = 10,
             .ansii_codes = {81, 81, 217, 217,  231, 231,  217, 217,  81, 81}
         },
+        .basic_codes = {14, 13, 15, 13, 14},
         .color_pattern = {
             .stripes_count = 5,
             .stripes_colors = {
@@ -99,6 +152,7 @@ stripe_flag! {
             .codes_count = 5,
             .ansii_codes = {196, 208, 255, 170, 128}
         },
+        .basic_codes = {9, 3, 15, 13, 5},
         .color_pattern = {
             .stripes_count = 5,
             .stripes_colors = {
@@ -290,14 +344,13 @@ pub const fn rainbow() -> Flag<'static> {
             39, 38, 44, 43, 49, 48, 84, 83, 119, 118, 154, 148, 184, 178, 214, 208, 209, 203, 204,
             198, 199, 163, 164, 128, 129, 93, 99, 63, 69, 33,
         ],
+        basic_colors: &[],
         stripe_colors: &[],
         factor: crate::Extended::ZERO,
         color_method: ColorMethod::Rainbow,
     }
 }
 
-use crate::color::Color;
-
 // An example of how to implement a custom flag.
 // Please use `#[must_use]` to make clippy happy
 #[must_use]
@@ -317,6 +370,8 @@ pub const fn dutch() -> Flag<'static> {
         name: "dutch",
         // you can repeat an ansi color multiple times to make the stripe thicker
         ansi_colors: &[52, 52, 255, 255, 17, 17],
+        // the basic colors that look the most like the stripes are used when this is empty
+        basic_colors: &[],
         stripe_colors: &STRIPES,
         factor: crate::Extended::lit("4.0"),
         color_method: ColorMethod::Stripes,
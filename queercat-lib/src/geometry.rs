@@ -0,0 +1,252 @@
+#![allow(clippy::must_use_candidate)]
+//! The shapes that the colors of a flag can be laid out in.
+//!
+//! Some geometries depend on the size of the text, so it has to be known before colorizing. It can be measured with
+//! [`TextSize::measure`] when the whole input is buffered.
+//!
+//! # Examples
+//! ```
+//! use queercat_lib::{
+//!     transgender, Bits24, Cursor, Geometry, Moving, QueerCat, QueerCatFrequency, TextSize,
+//! };
+//! let text = "trans rights\nare human rights\n";
+//! let size = TextSize::measure(text.as_bytes(), Cursor::new());
+//! assert_eq!((size.lines(), size.width()), (2, 16));
+//! let colorizer = Bits24::new(QueerCatFrequency::Original).with_geometry(Geometry::radial(&size));
+//! let mut cat = QueerCat::new(colorizer, Vec::new(), transgender());
+//! cat.cat(text.as_bytes())?;
+//! # Ok::<(), std::io::Error>(())
+//! ```
+
+use crate::colorizer::{Cursor, Position};
+use crate::escape::{Event, Parser};
+use std::convert::Infallible;
+use std::sync::Arc;
+use unicode_segmentation::UnicodeSegmentation;
+
+/// The furthest that the stripes geometries go, so that they don't wrap around to the first stripe
+const LAST: f64 = 1.0 - 1.0 / 4_294_967_296.0;
+
+/// Terminal cells are about twice as tall as they are wide, so a line counts as this many columns
+const LINE_HEIGHT: f64 = 2.0;
+
+/// The size of a block of text in the terminal
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TextSize {
+    line_widths: Vec<u32>,
+    width: u32,
+}
+
+impl TextSize {
+    /// A block of text with lines that are `line_widths` columns wide
+    pub fn from_line_widths(line_widths: Vec<u32>) -> Self {
+        let width = line_widths.iter().copied().max().unwrap_or(0);
+        Self { line_widths, width }
+    }
+
+    /// Measures `text` by moving `cursor` over it like a colorizer does. Escape sequences take up no space and
+    /// invalid utf-8 is measured like replacement characters.
+    /// A line break at the end of the text doesn't start another line.
+    pub fn measure(text: &[u8], mut cursor: Cursor) -> Self {
+        let mut line_widths = Vec::new();
+        let mut line_width = 0;
+        let mut line_started = false;
+        // only the text matters, so sequences don't need to be kept
+        let mut parser = Parser::new().with_limit(0);
+        parser
+            .parse(text, |event| {
+                if let Event::Text(text) = event {
+                    for grapheme in String::from_utf8_lossy(text).graphemes(true) {
+                        let line = cursor.line();
+                        cursor.advance(grapheme);
+                        if cursor.line() == line {
+                            line_width = line_width.max(cursor.col());
+                            line_started = true;
+                        } else {
+                            line_widths.push(line_width);
+                            line_width = 0;
+                            line_started = false;
+                        }
+                    }
+                }
+                Ok::<(), Infallible>(())
+            })
+            .unwrap_or_else(|e| match e {});
+        if line_started {
+            line_widths.push(line_width);
+        }
+        Self::from_line_widths(line_widths)
+    }
+
+    /// The number of lines
+    #[allow(clippy::cast_possible_truncation)]
+    pub fn lines(&self) -> u32 {
+        self.line_widths.len() as u32
+    }
+
+    /// The width of the longest line
+    pub const fn width(&self) -> u32 {
+        self.width
+    }
+
+    /// The width of every line
+    pub fn line_widths(&self) -> &[u32] {
+        &self.line_widths
+    }
+}
+
+/// How positions in the terminal are mapped to places on the flag
+#[derive(Debug, Clone, Default, PartialEq)]
+pub enum Geometry {
+    /// Diagonal stripes from the horizontal and vertical frequencies
+    #[default]
+    Linear,
+    /// Straight stripes that move in the direction of an angle in degrees, clockwise from left to right.
+    /// The speed is the horizontal frequency.
+    Angle(f32),
+    /// Rings around the center of a block of text that has `lines` lines and is `width` columns wide.
+    /// The speed is the horizontal frequency.
+    Radial { lines: u32, width: u32 },
+    /// Every line goes through the whole flag once, no matter how long it is.
+    /// Lines after the end of the text are as wide as its longest line.
+    PerLine(Arc<TextSize>),
+    /// The stripes of the flag from top to bottom across a block of text that has `lines` lines, like the actual
+    /// flag. With `blend`, the edges of the stripes are blended with [`Flag::factor`](crate::Flag::factor).
+    /// Lines after the end of the text get the last stripe.
+    Stripes { lines: u32, blend: bool },
+    /// The stripes of the flag from left to right across a block of text that is `width` columns wide
+    VerticalStripes { width: u32, blend: bool },
+}
+
+impl Geometry {
+    /// Rings around the center of a block of text of `size`
+    pub fn radial(size: &TextSize) -> Self {
+        Self::Radial {
+            lines: size.lines(),
+            width: size.width(),
+        }
+    }
+
+    /// Every line of a block of text of `size` goes through the whole flag once
+    pub fn per_line(size: impl Into<Arc<TextSize>>) -> Self {
+        Self::PerLine(size.into())
+    }
+
+    /// How far through the flag the grapheme that ends at `position` is, in cycles through the flag.
+    /// The steps are the number of cycles per column and per line.
+    pub(crate) fn cycles(&self, position: Position, col_step: f64, line_step: f64) -> f64 {
+        let col = f64::from(position.col);
+        let line = f64::from(position.line);
+        let cycles = match self {
+            Self::Linear => col * col_step + line * line_step,
+            Self::Angle(degrees) => {
+                let (sin, cos) = f64::from(*degrees).to_radians().sin_cos();
+                (col * cos + line * LINE_HEIGHT * sin) * col_step
+            }
+            Self::Radial { lines, width } => {
+                // columns are counted after the grapheme, so they start at 1
+                let x = col - f64::from(*width).midpoint(1.0);
+                let y = (line - (f64::from(*lines) - 1.0) / 2.0) * LINE_HEIGHT;
+                x.hypot(y) * col_step
+            }
+            Self::PerLine(size) => {
+                let width = size
+                    .line_widths()
+                    .get(position.line as usize)
+                    .copied()
+                    .unwrap_or(size.width());
+                (col - 1.0).max(0.0) / f64::from(width.max(1))
+            }
+            // the middle of the line or column, so that every stripe gets its fair share
+            Self::Stripes { lines, .. } => ((line + 0.5) / f64::from((*lines).max(1))).min(LAST),
+            Self::VerticalStripes { width, .. } => {
+                ((col - 0.5).max(0.0) / f64::from((*width).max(1))).min(LAST)
+            }
+        };
+        // a nonsense angle shouldn't break colorizing
+        if cycles.is_finite() {
+            cycles
+        } else {
+            0.0
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The cycles of every position in a block of `lines` lines and `width` columns, line by line
+    fn block(geometry: &Geometry, lines: u32, width: u32) -> Vec<Vec<f64>> {
+        (0..lines)
+            .map(|line| {
+                (1..=width)
+                    .map(|col| geometry.cycles(Position { line, col }, 0.1, 0.3))
+                    .collect()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn angles_move_along_their_direction() {
+        let across = block(&Geometry::Angle(0.0), 4, 6);
+        for line in &across {
+            assert_eq!(line, &across[0]);
+        }
+        assert!(across[0].windows(2).all(|pair| pair[0] < pair[1]));
+
+        let down = block(&Geometry::Angle(90.0), 4, 6);
+        for line in &down {
+            // the cosine of 90 degrees is only about 0
+            assert!(line.iter().all(|cycles| (cycles - line[0]).abs() < 1e-12));
+        }
+        assert!(down.windows(2).all(|pair| pair[0][0] < pair[1][0]));
+    }
+
+    #[test]
+    fn rings_are_symmetric_about_the_center() {
+        for text in ["ab\ncd\n", "trans\nrights\nare\nhuman\nrights\n", "x"] {
+            let size = TextSize::measure(text.as_bytes(), Cursor::new());
+            let (lines, width) = (size.lines(), size.width());
+            let rings = block(&Geometry::radial(&size), lines, width);
+            for (line, cycles) in rings.iter().enumerate() {
+                let mirrored = &rings[rings.len() - 1 - line];
+                for (col, &ring) in cycles.iter().enumerate() {
+                    assert_eq!(
+                        ring.to_bits(),
+                        cycles[cycles.len() - 1 - col].to_bits(),
+                        "{text:?}"
+                    );
+                    assert_eq!(ring.to_bits(), mirrored[col].to_bits(), "{text:?}");
+                }
+            }
+            // the middle is the closest to the center
+            let least = rings
+                .iter()
+                .flatten()
+                .copied()
+                .fold(f64::INFINITY, f64::min);
+            assert_eq!(
+                least.to_bits(),
+                rings[(lines as usize - 1) / 2][(width as usize - 1) / 2].to_bits()
+            );
+        }
+    }
+
+    #[test]
+    fn every_line_goes_through_the_whole_flag() {
+        let size = TextSize::from_line_widths(vec![3, 5, 1, 0]);
+        let geometry = Geometry::per_line(size.clone());
+        // lines after the end of the text are as wide as the longest line
+        for (line, width) in size.line_widths().iter().copied().chain([5]).enumerate() {
+            let line = u32::try_from(line).unwrap();
+            let cycles: Vec<f64> = (1..=width)
+                .map(|col| geometry.cycles(Position { line, col }, 0.1, 0.3))
+                .collect();
+            let expected: Vec<f64> = (0..width)
+                .map(|col| f64::from(col) / f64::from(width))
+                .collect();
+            assert_eq!(cycles, expected, "line {line}");
+        }
+    }
+}
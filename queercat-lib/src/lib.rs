@@ -9,7 +9,7 @@
 //! let writer = io::stdout().lock();
 //! let mut reader = io::stdin().lock();
 //! let flag = lesbian();
-//! let colorizer = Ansi::new(flag.ansi_colors.len() as u32, QueerCatFrequency::Original);
+//! let colorizer = Ansi::new(&flag, QueerCatFrequency::Original);
 //! let mut cat = QueerCat::new(colorizer, writer, flag);
 //! cat.cat(reader).unwrap();
 //! ```
@@ -26,14 +26,34 @@
 //! ```
 pub mod color;
 pub mod driver;
+pub mod escape;
 pub mod flag;
 pub use driver::*;
 pub use flag::*;
 pub mod colorizer;
 pub use colorizer::*;
-use fixed::{types::extra::U24, types::U0F32, FixedU32};
+pub mod writer;
+pub use writer::*;
+pub mod display;
+pub use display::*;
+mod dither;
+pub mod geometry;
+pub use geometry::*;
+pub mod animation;
+pub use animation::*;
+#[cfg(feature = "tokio")]
+pub mod async_io;
+#[cfg(feature = "tokio")]
+pub use async_io::*;
+#[cfg(feature = "rayon")]
+pub mod parallel;
+use fixed::{types::extra::U24, types::U0F32, FixedI64, FixedU32};
+#[cfg(feature = "rayon")]
+pub use parallel::*;
 
 /// Represents the 0-1 range of color values and theta
 pub type ColorV = U0F32;
 /// Big enough to hold an 8-bit integer and do precise enough calculations
 pub type Extended = FixedU32<U24>;
+/// Like [`Extended`], but signed and with room for far away lines and columns. Used for frequencies.
+pub type SignedExtended = FixedI64<U24>;
@@ -0,0 +1,241 @@
+//! Colorizing large inputs on multiple threads with rayon. Requires the `rayon` feature.
+//!
+//! The colors only depend on the line and column of each grapheme, so the input is split after line breaks and every
+//! chunk is colorized on its own, starting at its first line. With a [`Granularity`] other than graphemes, it is only
+//! split where a new unit starts. The output is exactly the same as the output of [`QueerCat`](crate::QueerCat).
+//!
+//! # Examples
+//! ```
+//! use queercat_lib::{transgender, Bits24, ParQueerCat, QueerCat, QueerCatFrequency};
+//! let text = "trans rights are human rights\n".repeat(1000);
+//! let colorizer = Bits24::new(QueerCatFrequency::Original);
+//! let mut par = ParQueerCat::new(colorizer.clone(), Vec::new(), transgender()).with_chunk_size(1024);
+//! par.cat(text.as_bytes())?;
+//! let mut cat = QueerCat::new(colorizer, Vec::new(), transgender());
+//! cat.cat(text.as_bytes())?;
+//! assert_eq!(par.into_inner(), cat.into_inner());
+//! # Ok::<(), std::io::Error>(())
+//! ```
+
+use crate::colorizer::Colorizer;
+use crate::driver::{Granularity, Painter};
+use crate::escape::{Event, Parser};
+use crate::flag::Flag;
+use rayon::prelude::*;
+use std::convert::Infallible;
+use std::io::{self, BufRead, Write};
+
+/// The default number of bytes that are colorized at once by a single thread
+pub const DEFAULT_CHUNK_SIZE: usize = 64 * 1024;
+
+/// The multithreaded version of [`QueerCat`](crate::QueerCat). Input is read in blocks of a few chunks per thread,
+/// so it is best for files and other input that is read much faster than it is colorized. Blocks that can't be split,
+/// like a single very long line, and all input when rayon only has one thread, are colorized on the calling thread.
+///
+/// Splitting and stitching the output has a cost, so whether it is faster than [`QueerCat`](crate::QueerCat) depends
+/// on the number of cores and the input. The `24 bit parallel` benchmarks compare them on the bench data.
+pub struct ParQueerCat<'a, W: Write, C: Colorizer> {
+    painter: Painter<'a, C>,
+    writer: W,
+    chunk_size: usize,
+    /// Input that has not been colorized yet. It starts where `painter` stopped, and is never much longer than a block.
+    pending: Vec<u8>,
+}
+
+impl<'a, W: Write, C> ParQueerCat<'a, W, C>
+where
+    C: Colorizer + Clone + Send,
+    C::Color: Send,
+{
+    #[must_use]
+    pub const fn new(colorizer: C, writer: W, flag: Flag<'a>) -> Self {
+        Self {
+            painter: Painter::new(colorizer, flag),
+            writer,
+            chunk_size: DEFAULT_CHUNK_SIZE,
+            pending: Vec::new(),
+        }
+    }
+
+    crate::driver::painter_builders! {
+        /// Sets whether the colors of the terminal are reset after each call to [`ParQueerCat::cat`]. Defaults to `true`.
+    }
+
+    /// Sets the number of bytes that a thread colorizes at once. Chunks always end after a line break, so they can
+    /// be longer. Defaults to [`DEFAULT_CHUNK_SIZE`]
+    #[must_use]
+    pub fn with_chunk_size(mut self, chunk_size: usize) -> Self {
+        self.chunk_size = chunk_size.max(1);
+        self
+    }
+
+    /// Returns the inner writer
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+
+    /// Colorizes the chunks of `self.pending` that end in a line break in parallel and keeps the rest
+    fn paint_pending(&mut self) -> Result<(), io::Error> {
+        let granularity = self.painter.granularity();
+        let parser = self.painter.parser().clone().with_limit(0);
+        let chunks = split_lines(&self.pending, self.chunk_size, parser, granularity);
+        let Some(&(end, _)) = chunks.last() else {
+            return Ok(());
+        };
+
+        // the first chunk continues from `self.painter`, the others start on their first line
+        let mut line = self.painter.colorizer().position().line;
+        let mut start = 0;
+        let mut rest = Vec::with_capacity(chunks.len() - 1);
+        for &(chunk_end, lines) in &chunks {
+            if start != 0 {
+                let chunk = &self.pending[start..chunk_end];
+                rest.push((line, self.painter.fork_at_line(line), chunk));
+            }
+            start = chunk_end;
+            line = line.wrapping_add(lines);
+        }
+
+        let first = &self.pending[..chunks[0].0];
+        let painter = &mut self.painter;
+        let ((output, res), rest) = rayon::join(
+            || paint(painter, first),
+            || {
+                rest.into_par_iter()
+                    .map(|(line, mut painter, chunk)| {
+                        let (output, res) = paint(&mut painter, chunk);
+                        (line, chunk[0], painter, output, res)
+                    })
+                    .collect::<Vec<_>>()
+            },
+        );
+        self.writer.write_all(&output)?;
+        res?;
+        for (line, first, painter, output, res) in rest {
+            // the painter wrote the color of its first unit even if the terminal already has it. Chunks start with
+            // a single byte grapheme when units are bigger than graphemes, see `split_lines`
+            let first = [first];
+            let first = std::str::from_utf8(&first).unwrap_or_default();
+            let repeated = self.painter.repeated_escape(line, first).min(output.len());
+            self.writer.write_all(&output[repeated..])?;
+            res?;
+            self.painter = painter;
+        }
+
+        self.pending.drain(..end);
+        Ok(())
+    }
+
+    fn cat_impl<R: BufRead>(&mut self, mut file: R) -> Result<(), io::Error> {
+        let threads = rayon::current_num_threads();
+        // enough input to give every thread a few chunks
+        let block_size = self.chunk_size.saturating_mul(threads * 4);
+        loop {
+            let buf = file.fill_buf()?;
+            let buf_len = buf.len();
+            if buf.is_empty() {
+                break;
+            }
+            if threads == 1 {
+                // there's nothing to gain from splitting the input
+                self.painter.write(buf, &mut self.writer)?;
+            } else {
+                self.pending.extend_from_slice(buf);
+            }
+            file.consume(buf_len);
+            if self.pending.len() >= block_size {
+                self.paint_pending()?;
+            }
+            if self.pending.len() >= block_size {
+                // the block can't be split, so it's colorized here instead of growing
+                self.painter.write(&self.pending, &mut self.writer)?;
+                self.pending.clear();
+            }
+        }
+        self.paint_pending()?;
+        // the last line doesn't end with a line break, so it's colorized on its own
+        self.painter.write(&self.pending, &mut self.writer)?;
+        self.painter.finish(&mut self.writer)
+    }
+
+    /// Colorizes input from `file` and writes it to the `writer`.
+    /// # Errors
+    /// Returns `Err` when writing with `self.writer` or reading `file` fails
+    pub fn cat<R: BufRead>(&mut self, file: R) -> Result<(), io::Error> {
+        let res = self.cat_impl(file);
+        self.pending.clear();
+        self.painter.write_reset(&mut self.writer)?;
+        res
+    }
+}
+
+/// Colorizes `chunk` into a buffer. What was colorized before an error is kept, so it can still be written.
+fn paint<C: Colorizer>(
+    painter: &mut Painter<'_, C>,
+    chunk: &[u8],
+) -> (Vec<u8>, Result<(), io::Error>) {
+    // most graphemes are a single byte, and colors take up to 19 bytes
+    let mut output = Vec::with_capacity(chunk.len() * 2);
+    let res = painter.write(chunk, &mut output);
+    (output, res)
+}
+
+/// Splits `input` after line breaks that are outside of escape sequences into chunks of at least `chunk_size` bytes.
+/// `parser` is in the state that the input starts in. Returns the end of every chunk and the number of line breaks in
+/// it. The input after the last chunk can't be split.
+fn split_lines(
+    input: &[u8],
+    chunk_size: usize,
+    mut parser: Parser,
+    granularity: Granularity,
+) -> Vec<(usize, u32)> {
+    let mut chunks = Vec::new();
+    let mut start = 0;
+    let mut parsed = 0;
+    let mut lines = 0;
+    loop {
+        let from = parsed.max(start + chunk_size);
+        let Some(line_end) = input
+            .get(from..)
+            .and_then(|rest| memchr::memchr(b'\n', rest))
+        else {
+            return chunks;
+        };
+        let end = from + line_end + 1;
+        parser
+            .parse(&input[parsed..end], |event| {
+                if let Event::Text(text) = event {
+                    #[allow(clippy::cast_possible_truncation)]
+                    let count = memchr::memchr_iter(b'\n', text).count() as u32;
+                    lines += count;
+                }
+                Ok::<(), Infallible>(())
+            })
+            .unwrap_or_else(|e| match e {});
+        parsed = end;
+        if !parser.in_sequence() && starts_unit(input, end, granularity) {
+            chunks.push((end, lines));
+            start = end;
+            lines = 0;
+        }
+    }
+}
+
+/// Whether a new unit surely starts at `end`, right after a line break. Bigger units than lines only start at a
+/// grapheme that isn't whitespace, so the line has to start with a printable ascii character. It is its own
+/// grapheme when an ascii character comes after it.
+fn starts_unit(input: &[u8], end: usize, granularity: Granularity) -> bool {
+    let printable = match input.get(end..end + 2) {
+        Some(&[first, second]) => first.is_ascii_graphic() && second.is_ascii(),
+        _ => false,
+    };
+    match granularity {
+        Granularity::Grapheme => true,
+        Granularity::Word | Granularity::Line => printable,
+        // paragraphs start after a blank line
+        Granularity::Paragraph => {
+            let before = &input[..end - 1];
+            printable && (before.ends_with(b"\n") || before.ends_with(b"\n\r"))
+        }
+    }
+}
@@ -0,0 +1,97 @@
+//! A colorizing adapter for [`Write`]rs
+//!
+//! # Examples
+//! ```
+//! use std::io::Write;
+//! use queercat_lib::{transgender, Bits24, ColorWriter, QueerCatFrequency};
+//! let colorizer = Bits24::new(QueerCatFrequency::Original);
+//! let mut writer = ColorWriter::new(colorizer, Vec::new(), transgender());
+//! writeln!(writer, "trans rights are human rights").unwrap();
+//! let colorized = writer.into_inner().unwrap();
+//! assert!(colorized.starts_with(b"\x1b[38;2;"));
+//! ```
+
+use crate::colorizer::Colorizer;
+use crate::driver::Painter;
+use crate::flag::Flag;
+use std::io::{self, Write};
+
+/// Colorizes everything that is written to it before passing it on to the inner writer.
+///
+/// Escapes, code points and graphemes that are split between writes are kept until the next write, so they are
+/// colorized like they would be by [`QueerCat`](crate::QueerCat). Flushing writes what is kept and resets the
+/// colors of the terminal. When a grapheme that was flushed continues in the next write, the rest of it is written
+/// without a color in between, so flushing never splits a grapheme. The writer is also flushed when it is dropped,
+/// but errors are ignored, so use [`ColorWriter::into_inner`] to handle them.
+pub struct ColorWriter<'a, W: Write, C: Colorizer> {
+    painter: Painter<'a, C>,
+    /// Only `None` after `into_inner`
+    writer: Option<W>,
+}
+
+impl<'a, W: Write, C: Colorizer> ColorWriter<'a, W, C> {
+    #[must_use]
+    pub const fn new(colorizer: C, writer: W, flag: Flag<'a>) -> Self {
+        Self {
+            painter: Painter::new(colorizer, flag),
+            writer: Some(writer),
+        }
+    }
+
+    crate::driver::painter_builders! {
+        /// Sets whether the colors of the terminal are reset after flushing. Defaults to `true`.
+    }
+
+    /// A reference to the inner writer
+    pub fn get_ref(&self) -> &W {
+        self.writer.as_ref().unwrap_or_else(|| taken())
+    }
+
+    /// A mutable reference to the inner writer. Writing to it directly can mess up the colors.
+    pub fn get_mut(&mut self) -> &mut W {
+        self.writer.as_mut().unwrap_or_else(|| taken())
+    }
+
+    /// Ends the input and returns the inner writer. Unfinished code points are handled like invalid utf-8 and
+    /// unfinished escapes are discarded.
+    /// # Errors
+    /// Returns `Err` when writing to or flushing the inner writer fails
+    pub fn into_inner(mut self) -> Result<W, io::Error> {
+        self.finish()?;
+        Ok(self.writer.take().unwrap_or_else(|| taken()))
+    }
+
+    fn finish(&mut self) -> Result<(), io::Error> {
+        let writer = self.writer.as_mut().unwrap_or_else(|| taken());
+        self.painter.finish(writer)?;
+        self.painter.flush(writer)?;
+        writer.flush()
+    }
+}
+
+/// The writer is only `None` after `into_inner`, which consumes the `ColorWriter`
+fn taken() -> ! {
+    unreachable!("the writer is only taken by `into_inner`")
+}
+
+impl<W: Write, C: Colorizer> Write for ColorWriter<'_, W, C> {
+    fn write(&mut self, buf: &[u8]) -> Result<usize, io::Error> {
+        let writer = self.writer.as_mut().unwrap_or_else(|| taken());
+        self.painter.write(buf, writer)?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> Result<(), io::Error> {
+        let writer = self.writer.as_mut().unwrap_or_else(|| taken());
+        self.painter.flush(writer)?;
+        writer.flush()
+    }
+}
+
+impl<W: Write, C: Colorizer> Drop for ColorWriter<'_, W, C> {
+    fn drop(&mut self) {
+        if self.writer.is_some() {
+            let _ = self.finish();
+        }
+    }
+}
@@ -0,0 +1,99 @@
+//! Colorizing should not allocate once the buffers of the driver have grown to fit the input
+
+use queercat_lib::color::{Gradient, DEFAULT_GRADIENT_RESOLUTION};
+use queercat_lib::*;
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::cell::Cell;
+use std::io::{self, BufReader, Write};
+
+struct CountingAllocator;
+
+thread_local! {
+    static ALLOCATIONS: Cell<usize> = const { Cell::new(0) };
+}
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.with(|count| count.set(count.get() + 1));
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout);
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        ALLOCATIONS.with(|count| count.set(count.get() + 1));
+        System.realloc(ptr, layout, new_size)
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: CountingAllocator = CountingAllocator;
+
+/// The number of allocations that `f` makes on this thread
+fn allocations(f: impl FnOnce()) -> usize {
+    let before = ALLOCATIONS.with(Cell::get);
+    f();
+    ALLOCATIONS.with(Cell::get) - before
+}
+
+fn input() -> Vec<u8> {
+    let mut input = Vec::new();
+    input.extend_from_slice(include_bytes!("../benches/bench-data/escape"));
+    input.extend_from_slice(include_bytes!("../benches/bench-data/cyrillic"));
+    input.extend_from_slice(include_bytes!("../benches/bench-data/queercat"));
+    // invalid utf-8, combining characters, tabs and a hyperlink
+    input.extend_from_slice(
+        b"\xff\xfe a\xcc\x81\te\xe2\x80\x8d\r\n\x1b]8;;https://example.com\x1b\\link\x1b]8;;\x07\n",
+    );
+    input
+}
+
+/// Colorizes `input` twice with `cat` through small reads. Only the first run may allocate.
+fn assert_steady<C: Colorizer>(mut cat: QueerCat<'_, io::Sink, C>, input: &[u8]) {
+    for read_size in [7, 4096] {
+        let mut first = BufReader::with_capacity(read_size, input);
+        let mut second = BufReader::with_capacity(read_size, input);
+        cat.colorizer_mut().seek(Position::default());
+        cat.cat(&mut first).unwrap();
+        cat.colorizer_mut().seek(Position::default());
+        assert_eq!(allocations(|| cat.cat(&mut second).unwrap()), 0);
+    }
+}
+
+#[test]
+fn cat_does_not_allocate() {
+    let input = input();
+    let flag = transgender();
+    let ansi = || Ansi::new(&flag, QueerCatFrequency::Original);
+    let bits24 = || Bits24::new(QueerCatFrequency::Original);
+    let gradient = Gradient::new(&flag, DEFAULT_GRADIENT_RESOLUTION);
+
+    for escape_mode in [EscapeMode::Strip, EscapeMode::Passthrough] {
+        let cat = QueerCat::new(ansi(), io::sink(), flag.clone()).with_escape_mode(escape_mode);
+        assert_steady(cat, &input);
+        let cat = QueerCat::new(bits24(), io::sink(), flag.clone()).with_escape_mode(escape_mode);
+        assert_steady(cat, &input);
+        let colorizer = Bits24Lut::new(bits24(), gradient.clone());
+        let cat = QueerCat::new(colorizer, io::sink(), flag.clone()).with_escape_mode(escape_mode);
+        assert_steady(cat, &input);
+    }
+}
+
+#[test]
+fn color_writer_does_not_allocate() {
+    let input = input();
+    let colorizer = Bits24::new(QueerCatFrequency::Original);
+    let mut writer = ColorWriter::new(colorizer, io::sink(), transgender())
+        .with_escape_mode(EscapeMode::Passthrough);
+    let write_all = |writer: &mut ColorWriter<'_, io::Sink, Bits24>| {
+        for chunk in input.chunks(5) {
+            writer.write_all(chunk).unwrap();
+        }
+        writer.flush().unwrap();
+    };
+    write_all(&mut writer);
+    writer.colorizer_mut().seek(Position::default());
+    assert_eq!(allocations(|| write_all(&mut writer)), 0);
+}
@@ -0,0 +1,144 @@
+//! Every frame of an animated line is drawn over the last one with the gradient moved a step further
+
+use queercat_lib::*;
+use std::io::{self, Write};
+
+const STEP: f32 = 0.25;
+
+/// A writer that keeps what was written between flushes apart
+#[derive(Default)]
+struct Frames {
+    frames: Vec<Vec<u8>>,
+    current: Vec<u8>,
+}
+
+impl Write for Frames {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.current.extend_from_slice(buf);
+        Ok(buf.len())
+    }
+    fn flush(&mut self) -> io::Result<()> {
+        self.frames.push(std::mem::take(&mut self.current));
+        Ok(())
+    }
+}
+
+/// An animation that doesn't wait between frames, and the offsets that it made colorizers with
+fn animation(
+    duration: u32,
+    escape_mode: EscapeMode,
+) -> Animation<'static, Bits24, impl FnMut(f32) -> Bits24> {
+    let colorizer = Bits24::new(QueerCatFrequency::Original);
+    Animation::new(transgender(), move |offset| {
+        colorizer.clone().with_offset(offset)
+    })
+    .with_escape_mode(escape_mode)
+    .with_duration(duration)
+    .with_speed(0.0)
+    .with_step(STEP)
+}
+
+/// The frames of animating `line` as the line at `line_number`
+fn frames(
+    line: &[u8],
+    line_number: u32,
+    duration: u32,
+    escape_mode: EscapeMode,
+) -> io::Result<Vec<Vec<u8>>> {
+    let mut writer = Frames::default();
+    animation(duration, escape_mode).animate_line(line, line_number, &mut writer)?;
+    assert!(writer.current.is_empty(), "every frame is flushed");
+    Ok(writer.frames)
+}
+
+#[test]
+fn frames_are_drawn_over_each_other() -> io::Result<()> {
+    let line = b"trans rights";
+    let frames = frames(line, 3, 4, EscapeMode::Strip)?;
+    assert_eq!(frames.len(), 4);
+    for (i, frame) in frames.iter().enumerate() {
+        // the first frame saves the position of the cursor, and the others go back to it
+        let prefix: &[u8] = if i == 0 { b"\x1b7" } else { b"\x1b8" };
+        assert!(frame.starts_with(prefix), "{i}");
+        assert!(frame.ends_with(b"\x1b[0m"), "{i}");
+        let mut expected = Vec::new();
+        #[allow(clippy::cast_precision_loss)]
+        let offset = STEP * i as f32;
+        animation(1, EscapeMode::Strip).render_line(line, 3, offset, &mut expected)?;
+        assert_eq!(&frame[prefix.len()..], expected, "{i}");
+    }
+    Ok(())
+}
+
+#[test]
+fn the_gradient_moves_every_frame() -> io::Result<()> {
+    let mut offsets = Vec::new();
+    let colorizer = Bits24::new(QueerCatFrequency::Original);
+    let mut animation = Animation::new(transgender(), |offset| {
+        offsets.push(offset);
+        colorizer.clone().with_offset(offset)
+    })
+    .with_duration(5)
+    .with_speed(0.0)
+    .with_step(STEP);
+    animation.animate_line(b"trans rights", 0, &mut io::sink())?;
+    let expected = [0.0, 0.25, 0.5, 0.75, 1.0].map(f32::to_bits);
+    assert_eq!(
+        offsets
+            .iter()
+            .map(|offset| offset.to_bits())
+            .collect::<Vec<_>>(),
+        expected
+    );
+
+    // a whole flag later, the frame looks like the first one again
+    let drawn = frames(b"trans rights", 0, 5, EscapeMode::Strip)?;
+    assert_eq!(drawn[0][2..], drawn[4][2..]);
+    for pair in drawn[..4].windows(2) {
+        assert_ne!(pair[0][2..], pair[1][2..]);
+    }
+    // the line number moves the gradient like it does in the whole text
+    assert_ne!(
+        frames(b"trans rights", 1, 1, EscapeMode::Strip)?,
+        drawn[..1]
+    );
+    Ok(())
+}
+
+#[test]
+fn lines_without_text() -> io::Result<()> {
+    assert!(frames(b"", 0, 3, EscapeMode::Strip)?.is_empty());
+    let mut output = Vec::new();
+    animation(3, EscapeMode::Strip).animate(&b"\n\n"[..], &mut output)?;
+    assert_eq!(output, b"\x1b[?25l\n\n\x1b[0m\x1b[?25h");
+
+    // escapes are drawn in every frame when they are passed through, and nothing is colorized
+    let bold = b"\x1b[1m";
+    for (escape_mode, expected) in [
+        (EscapeMode::Strip, &b""[..]),
+        (EscapeMode::Passthrough, bold),
+    ] {
+        let frames = frames(bold, 0, 3, escape_mode)?;
+        assert_eq!(frames.len(), 3);
+        for frame in &frames {
+            assert!(
+                !frame.windows(7).any(|w| w == b"\x1b[38;2;"),
+                "{escape_mode:?}"
+            );
+            assert!(frame[2..].starts_with(expected), "{escape_mode:?}");
+        }
+    }
+    Ok(())
+}
+
+#[test]
+fn zero_duration_draws_one_frame() -> io::Result<()> {
+    let frames = frames(b"trans rights", 0, 0, EscapeMode::Strip)?;
+    assert_eq!(frames.len(), 1);
+    assert!(frames[0].starts_with(b"\x1b7"));
+    let mut output = Vec::new();
+    animation(0, EscapeMode::Strip).animate(&b"trans rights\n"[..], &mut output)?;
+    assert_eq!(output.windows(2).filter(|w| w == b"\x1b7").count(), 1);
+    assert!(output.ends_with(b"\n\x1b[0m\x1b[?25h"));
+    Ok(())
+}
@@ -0,0 +1,177 @@
+//! The async colorizers write exactly the same bytes as the blocking ones, whatever the chunks of input and however
+//! slow the writer is
+#![cfg(feature = "tokio")]
+
+use queercat_lib::*;
+use std::io::{self, Write};
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use tokio::io::{AsyncBufRead, AsyncRead, AsyncWrite, AsyncWriteExt, ReadBuf};
+
+/// Changes the color of every grapheme
+const FREQ: QueerCatFrequency = QueerCatFrequency::Custom(0.3, 0.07);
+
+/// Zero width joiner sequences, combining characters, escapes and `\r\n`, also right at the end
+const TEXT: &str = "a👩‍👩‍👧e\u{301}\x1b[1mbold\x1b[0m 🏳️‍⚧️x\r\ntrans rights\nne\u{301}\u{302}";
+
+/// Runs `future` to completion on a single thread
+fn block_on<F: std::future::Future>(future: F) -> F::Output {
+    tokio::runtime::Builder::new_current_thread()
+        .build()
+        .expect("the runtime should start")
+        .block_on(future)
+}
+
+/// A writer that is only ready every other time it is polled, and then only takes up to `max` bytes
+struct Slow {
+    written: Vec<u8>,
+    max: usize,
+    ready: bool,
+}
+
+impl Slow {
+    const fn new(max: usize) -> Self {
+        Self {
+            written: Vec::new(),
+            max,
+            ready: false,
+        }
+    }
+
+    /// Whether this poll is ready, waking the task up again when it isn't
+    fn poll_ready(&mut self, cx: &Context<'_>) -> Poll<()> {
+        self.ready = !self.ready;
+        if self.ready {
+            Poll::Ready(())
+        } else {
+            cx.waker().wake_by_ref();
+            Poll::Pending
+        }
+    }
+}
+
+impl AsyncWrite for Slow {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<Result<usize, io::Error>> {
+        let this = self.get_mut();
+        std::task::ready!(this.poll_ready(cx));
+        let len = buf.len().min(this.max);
+        this.written.extend_from_slice(&buf[..len]);
+        Poll::Ready(Ok(len))
+    }
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), io::Error>> {
+        self.get_mut().poll_ready(cx).map(Ok)
+    }
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), io::Error>> {
+        self.get_mut().poll_ready(cx).map(Ok)
+    }
+}
+
+/// A reader that returns one chunk per read, and isn't ready before every chunk
+struct Chunks<'a> {
+    chunks: Vec<&'a [u8]>,
+    ready: bool,
+}
+
+impl AsyncRead for Chunks<'_> {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        let chunk = std::task::ready!(self.as_mut().poll_fill_buf(cx))?;
+        let len = chunk.len().min(buf.remaining());
+        buf.put_slice(&chunk[..len]);
+        self.consume(len);
+        Poll::Ready(Ok(()))
+    }
+}
+
+impl AsyncBufRead for Chunks<'_> {
+    fn poll_fill_buf(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<&[u8]>> {
+        let this = self.get_mut();
+        if !this.ready {
+            this.ready = true;
+            cx.waker().wake_by_ref();
+            return Poll::Pending;
+        }
+        Poll::Ready(Ok(this.chunks.first().copied().unwrap_or_default()))
+    }
+    fn consume(self: Pin<&mut Self>, amt: usize) {
+        let this = self.get_mut();
+        if let Some(chunk) = this.chunks.first_mut() {
+            *chunk = &chunk[amt..];
+            if chunk.is_empty() {
+                this.chunks.remove(0);
+                this.ready = false;
+            }
+        }
+    }
+}
+
+/// Every way of splitting the text into two chunks, and into chunks of one byte
+fn splits() -> impl Iterator<Item = Vec<&'static [u8]>> {
+    let text = TEXT.as_bytes();
+    (1..text.len())
+        .map(|split| {
+            let (a, b) = text.split_at(split);
+            vec![a, b]
+        })
+        .chain(std::iter::once(text.chunks(1).collect()))
+}
+
+/// What [`ColorWriter`] writes for the text with `escape_mode`
+fn blocking(escape_mode: EscapeMode) -> io::Result<Vec<u8>> {
+    let mut writer = ColorWriter::new(Bits24::new(FREQ), Vec::new(), transgender())
+        .with_escape_mode(escape_mode);
+    writer.write_all(TEXT.as_bytes())?;
+    writer.into_inner()
+}
+
+#[test]
+fn color_writers_write_the_same_bytes() -> io::Result<()> {
+    for escape_mode in [EscapeMode::Strip, EscapeMode::Passthrough] {
+        let expected = blocking(escape_mode)?;
+        for max in [1, 3, usize::MAX] {
+            for chunks in splits() {
+                let slow = block_on(async {
+                    let mut writer =
+                        AsyncColorWriter::new(Bits24::new(FREQ), Slow::new(max), transgender())
+                            .with_escape_mode(escape_mode);
+                    for chunk in &chunks {
+                        writer.write_all(chunk).await?;
+                    }
+                    writer.shutdown().await?;
+                    Ok::<_, io::Error>(writer.into_inner().written)
+                })?;
+                assert_eq!(slow, expected, "{escape_mode:?} {max} {chunks:?}");
+            }
+        }
+    }
+    Ok(())
+}
+
+#[test]
+fn cats_write_the_same_bytes() -> io::Result<()> {
+    let mut expected = Vec::new();
+    QueerCat::new(Bits24::new(FREQ), &mut expected, transgender()).cat(TEXT.as_bytes())?;
+    assert_eq!(expected, blocking(EscapeMode::Strip)?);
+    for max in [1, 3, usize::MAX] {
+        for chunks in splits() {
+            let reader = Chunks {
+                chunks: chunks.clone(),
+                ready: false,
+            };
+            let slow = block_on(async {
+                let mut cat = AsyncQueerCat::new(Bits24::new(FREQ), Slow::new(max), transgender());
+                cat.cat(reader).await?;
+                Ok::<_, io::Error>(cat.into_inner().written)
+            })?;
+            assert_eq!(slow, expected, "{max} {chunks:?}");
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,85 @@
+//! Basic colors write the codes of the 16 color palette, and flags without a table get the closest of them
+
+use queercat_lib::color::{BasicColor, Color, BASIC_PALETTE};
+use queercat_lib::*;
+
+/// The escapes that colorizing the flag as horizontal stripes writes, one per stripe
+fn stripes(flag: Flag<'_>) -> Result<Vec<String>, std::io::Error> {
+    let lines = u32::try_from(flag.stripe_colors.len()).unwrap();
+    let basic = Basic::new(&flag, QueerCatFrequency::Original).with_geometry(Geometry::Stripes {
+        lines,
+        blend: false,
+    });
+    let mut output = Vec::new();
+    QueerCat::new(basic, &mut output, flag).cat("trans rights\n".repeat(5).as_bytes())?;
+    let output = String::from_utf8_lossy(&output).into_owned();
+    Ok(output
+        .split_inclusive('m')
+        .filter_map(|run| run.find('\x1b').map(|start| run[start..].to_owned()))
+        .filter(|escape| escape != "\x1b[0m")
+        .collect())
+}
+
+#[test]
+fn codes_are_normal_then_bright() {
+    let codes: Vec<String> = (0..16).map(|i| BasicColor(i).to_string()).collect();
+    let expected: Vec<String> = (30..38)
+        .chain(90..98)
+        .map(|code| format!("\x1b[{code}m"))
+        .collect();
+    assert_eq!(codes, expected);
+    // higher numbers wrap around
+    assert_eq!(BasicColor(16).to_string(), "\x1b[30m");
+    assert_eq!(BasicColor(255).to_string(), "\x1b[97m");
+    assert_eq!(BasicColor(25).color(), BASIC_PALETTE[9]);
+}
+
+#[test]
+fn flags_use_their_basic_codes() -> Result<(), std::io::Error> {
+    assert_eq!(
+        stripes(transgender())?,
+        ["\x1b[96m", "\x1b[95m", "\x1b[97m", "\x1b[95m", "\x1b[96m"]
+    );
+    assert_eq!(
+        stripes(lesbian())?,
+        ["\x1b[91m", "\x1b[33m", "\x1b[97m", "\x1b[95m", "\x1b[35m"]
+    );
+    Ok(())
+}
+
+#[test]
+fn nearest_picks_the_closest_basic_color() {
+    for (i, &color) in BASIC_PALETTE.iter().enumerate() {
+        assert_eq!(usize::from(BasicColor::nearest(color).0), i, "{i}");
+    }
+    assert_eq!(
+        BasicColor::nearest(Color::from_hex(0xff_00_00)),
+        BasicColor(9)
+    );
+    assert_eq!(
+        BasicColor::nearest(Color::from_hex(0xc0_00_00)),
+        BasicColor(1)
+    );
+    assert_eq!(
+        BasicColor::nearest(Color::from_hex(0x10_10_10)),
+        BasicColor(0)
+    );
+    // the stripes of flags without a table get the closest colors
+    let flag = Flag {
+        name: "",
+        ansi_colors: &[],
+        basic_colors: &[],
+        stripe_colors: &[Color::from_hex(0xff_00_00), Color::from_hex(0x00_00_ff)],
+        color_method: ColorMethod::Stripes,
+        factor: Extended::lit("4"),
+    };
+    let mut output = Vec::new();
+    QueerCat::new(
+        Basic::new(&flag, QueerCatFrequency::Original),
+        &mut output,
+        flag,
+    )
+    .cat(&b"x"[..])
+    .unwrap();
+    assert!(output.starts_with(b"\x1b[91m"));
+}
@@ -0,0 +1,105 @@
+//! Flags that only have some of their colors, like the custom flags of the commandline tool, work with every
+//! colorizer
+
+use queercat_lib::color::{AnsiColor, Color};
+use queercat_lib::*;
+
+const STRIPES: [Color; 3] = [
+    Color::from_hex(0xff0000),
+    Color::from_hex(0xffffff),
+    Color::from_hex(0x0000ff),
+];
+
+fn flag(stripe_colors: &[Color]) -> Flag<'_> {
+    Flag {
+        name: "custom",
+        ansi_colors: &[],
+        basic_colors: &[],
+        stripe_colors,
+        factor: Extended::lit("4.0"),
+        color_method: ColorMethod::Stripes,
+    }
+}
+
+const TEXT: &str = "a flag with only some of its colors\n\tstill gets colorized\n";
+
+#[test]
+fn flags_with_only_stripe_colors() {
+    let flag = flag(&STRIPES);
+    let derived = flag.derive_ansi_colors();
+    let colorized = colorize(TEXT, &flag, ColorizeOptions::default());
+    assert!(colorized.starts_with(&AnsiColor(derived[0]).to_string()));
+    for depth in [ColorDepth::Bits24, ColorDepth::Basic] {
+        let options = ColorizeOptions {
+            depth,
+            ..ColorizeOptions::default()
+        };
+        assert!(colorize(TEXT, &flag, options).starts_with("\x1b["));
+    }
+}
+
+#[test]
+fn flags_with_a_single_color() -> Result<(), std::io::Error> {
+    let red = [Color::from_hex(0xff0000)];
+    let flag = flag(&red);
+    assert_eq!(Color::from_flag(ColorV::from_num(0.7), &flag), red[0]);
+    for depth in [ColorDepth::Ansi, ColorDepth::Bits24, ColorDepth::Basic] {
+        let options = ColorizeOptions {
+            depth,
+            ..ColorizeOptions::default()
+        };
+        let colorized = colorize(TEXT, &flag, options);
+        // every grapheme has the same color
+        assert_eq!(colorized.matches("\x1b[").count(), 2, "{depth:?}");
+    }
+    let dithered = Basic::new(&flag, QueerCatFrequency::Original).with_dither(true);
+    QueerCat::new(dithered, std::io::sink(), flag.clone()).cat(TEXT.as_bytes())
+}
+
+#[test]
+fn flags_without_colors_are_rainbows() {
+    let empty = flag(&[]);
+    let rainbow = rainbow();
+    let theta = ColorV::from_num(0.3);
+    assert_eq!(
+        Color::from_flag(theta, &empty),
+        Color::from_flag(theta, &rainbow)
+    );
+    assert_eq!(
+        Color::from_stripes(theta, &empty, true),
+        Color::from_flag(theta, &rainbow)
+    );
+    assert_eq!(empty.derive_ansi_colors(), rainbow.derive_ansi_colors());
+    let options = ColorizeOptions {
+        depth: ColorDepth::Bits24,
+        ..ColorizeOptions::default()
+    };
+    assert_eq!(
+        colorize(TEXT, &empty, options),
+        colorize(TEXT, &rainbow, options)
+    );
+}
+
+#[test]
+fn dithering_and_stripes_without_colors() -> Result<(), std::io::Error> {
+    let empty = flag(&[]);
+    for geometry in [
+        Geometry::Linear,
+        Geometry::Stripes {
+            lines: 2,
+            blend: true,
+        },
+    ] {
+        let ansi = Ansi::new(&empty, QueerCatFrequency::Original)
+            .with_geometry(geometry.clone())
+            .with_dither(true);
+        QueerCat::new(ansi, std::io::sink(), empty.clone()).cat(TEXT.as_bytes())?;
+        let basic = Basic::new(&empty, QueerCatFrequency::Original)
+            .with_geometry(geometry.clone())
+            .with_dither(true);
+        QueerCat::new(basic, std::io::sink(), empty.clone()).cat(TEXT.as_bytes())?;
+        let bits24 = Bits24::new(QueerCatFrequency::Original).with_geometry(geometry);
+        QueerCat::new(bits24, std::io::sink(), empty.clone()).cat(TEXT.as_bytes())?;
+    }
+    Ok(())
+}
@@ -0,0 +1,94 @@
+//! Colors keep changing smoothly far into very long inputs instead of overflowing
+
+use queercat_lib::*;
+
+/// Close to the last line and column that a cursor can be at
+const FAR: Position = Position {
+    line: u32::MAX - 1,
+    col: u32::MAX - 1000,
+};
+
+const FREQUENCIES: [QueerCatFrequency; 4] = [
+    QueerCatFrequency::Original,
+    QueerCatFrequency::Aaaaaaaaaaaaa,
+    QueerCatFrequency::Custom(-0.23, -0.1),
+    QueerCatFrequency::Custom(300.5, 250.25),
+];
+
+/// The states of `count` graphemes in a row, starting at `position`
+fn states<C: Colorizer>(mut colorizer: C, position: Position, count: usize) -> Vec<C::State> {
+    colorizer.seek(position);
+    (0..count).map(|_| colorizer.update_state("a")).collect()
+}
+
+#[test]
+fn ansi_follows_bits24_far_away() {
+    let flag = transgender();
+    let flag_len = flag.ansi_colors.len();
+    for freq in FREQUENCIES {
+        let ansi = Ansi::new(&flag, freq);
+        let places = states(ansi.clone(), FAR, 1000);
+        let thetas = states(Bits24::new(freq), FAR, 1000);
+        for (place, theta) in places.into_iter().zip(thetas) {
+            assert_eq!(place.theta, theta, "{freq:?}");
+            #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+            let index = (theta.to_num::<f64>() * flag_len as f64) as usize;
+            assert_eq!(
+                ansi.calculate_color(place, &flag).0,
+                flag.ansi_colors[index]
+            );
+        }
+    }
+}
+
+#[test]
+fn bits24_is_continuous_far_away() {
+    for freq in FREQUENCIES {
+        let states = states(Bits24::new(freq), FAR, 1000);
+        let step = states[1].wrapping_sub(states[0]);
+        for pair in states.windows(2) {
+            assert_eq!(pair[1].wrapping_sub(pair[0]), step, "{freq:?}");
+        }
+    }
+}
+
+#[test]
+fn huge_offsets_wrap_around() {
+    let flag = transgender();
+    for freq in FREQUENCIES {
+        // 2^22 + 0.5 is the largest f32 with a half
+        for (huge, small) in [
+            (4_194_304.5, 0.5),
+            (1e12, 0.0),
+            (-1e20, 0.0),
+            (f32::MAX, 0.0),
+        ] {
+            let far = states(Bits24::new(freq).with_offset(huge), FAR, 100);
+            assert_eq!(
+                far,
+                states(Bits24::new(freq).with_offset(small), FAR, 100),
+                "{freq:?} {huge}"
+            );
+            let ansi = |offset| Ansi::new(&flag, freq).with_offset(offset);
+            let colors = |offset| {
+                let ansi = ansi(offset);
+                states(ansi.clone(), FAR, 100)
+                    .into_iter()
+                    .map(|place| ansi.calculate_color(place, &flag))
+                    .collect::<Vec<_>>()
+            };
+            assert_eq!(colors(huge), colors(small), "{freq:?} {huge}");
+        }
+    }
+}
+
+#[test]
+fn long_lines_and_many_lines() -> Result<(), std::io::Error> {
+    let mut input = "a".repeat(200_000);
+    input.push_str(&"a\n".repeat(50_000));
+    let flag = transgender();
+    let ansi = Ansi::new(&flag, QueerCatFrequency::Fast);
+    QueerCat::new(ansi, std::io::sink(), flag.clone()).cat(input.as_bytes())?;
+    let bits24 = Bits24::new(QueerCatFrequency::Fast);
+    QueerCat::new(bits24, std::io::sink(), flag).cat(input.as_bytes())
+}
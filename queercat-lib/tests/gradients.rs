@@ -0,0 +1,43 @@
+//! Colorizers that look their colors up in a gradient write the same escapes as ones that calculate them
+
+use queercat_lib::color::{Gradient, DEFAULT_GRADIENT_RESOLUTION};
+use queercat_lib::*;
+
+/// The output of colorizing `text` with `colorizer`
+fn cat<C: Colorizer>(colorizer: C, text: &str) -> Result<String, std::io::Error> {
+    let mut output = Vec::new();
+    QueerCat::new(colorizer, &mut output, transgender()).cat(text.as_bytes())?;
+    Ok(String::from_utf8_lossy(&output).into_owned())
+}
+
+#[test]
+fn samples_with_the_same_color_write_one_escape() -> Result<(), std::io::Error> {
+    let flag = transgender();
+    let text = "trans rights\n".repeat(5);
+    for geometry in [
+        Geometry::Stripes {
+            lines: 5,
+            blend: false,
+        },
+        Geometry::VerticalStripes {
+            width: 12,
+            blend: false,
+        },
+    ] {
+        let bits24 = Bits24::new(QueerCatFrequency::Original).with_geometry(geometry.clone());
+        let gradient = Gradient::from_fn(DEFAULT_GRADIENT_RESOLUTION, |theta| {
+            bits24.calculate_color(theta, &flag)
+        });
+        let lut = cat(Bits24Lut::new(bits24.clone(), gradient), &text)?;
+        assert_eq!(lut, cat(bits24, &text)?, "{geometry:?}");
+        let escapes = lut.matches("\x1b[38;2;").count();
+        // the line breaks are in the last vertical stripe, like the starts of the lines after them
+        let expected = if matches!(geometry, Geometry::Stripes { .. }) {
+            5
+        } else {
+            1 + 5 * 4
+        };
+        assert_eq!(escapes, expected, "{geometry:?}");
+    }
+    Ok(())
+}
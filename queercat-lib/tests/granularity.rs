@@ -0,0 +1,137 @@
+//! Whole words, lines and paragraphs get the color of their first grapheme, even when they are split between writes
+//! or the colors are reset in the middle of them
+
+use queercat_lib::*;
+use std::io::Write;
+
+/// Changes the color of every grapheme
+const FREQ: QueerCatFrequency = QueerCatFrequency::Custom(0.3, 0.07);
+
+/// The text that comes after every escape, with the escape
+fn runs(output: &[u8]) -> Vec<(String, String)> {
+    let output = String::from_utf8_lossy(output);
+    output
+        .split('\x1b')
+        .filter(|run| !run.is_empty())
+        .map(|run| {
+            let end = run.find('m').map_or(0, |m| m + 1);
+            (run[..end].to_owned(), run[end..].to_owned())
+        })
+        .collect()
+}
+
+/// Colorizes the chunks one write at a time
+fn write(chunks: &[&str], granularity: Granularity, escape_mode: EscapeMode) -> Vec<u8> {
+    let mut writer = ColorWriter::new(Bits24::new(FREQ), Vec::new(), transgender())
+        .with_granularity(granularity)
+        .with_escape_mode(escape_mode);
+    for chunk in chunks {
+        writer.write_all(chunk.as_bytes()).unwrap();
+    }
+    writer.into_inner().unwrap()
+}
+
+fn colorize(text: &str, granularity: Granularity) -> Vec<u8> {
+    write(&[text], granularity, EscapeMode::Strip)
+}
+
+#[test]
+fn every_unit_gets_one_color() {
+    let text = "trans rights  are\nhuman rights\n\n\nand  always\nwill be\n";
+    for (granularity, units) in [
+        (
+            Granularity::Word,
+            vec![
+                "trans ",
+                "rights  ",
+                "are\n",
+                "human ",
+                "rights\n\n\n",
+                "and  ",
+                "always\n",
+                "will ",
+                "be\n",
+            ],
+        ),
+        (
+            Granularity::Line,
+            vec![
+                "trans rights  are\n",
+                "human rights\n",
+                "\n",
+                "\n",
+                "and  always\n",
+                "will be\n",
+            ],
+        ),
+        (
+            Granularity::Paragraph,
+            vec![
+                "trans rights  are\nhuman rights\n\n\n",
+                "and  always\nwill be\n",
+            ],
+        ),
+    ] {
+        let runs = runs(&colorize(text, granularity));
+        let texts: Vec<&str> = runs.iter().map(|(_, text)| text.as_str()).collect();
+        // the last run is after the reset
+        assert_eq!(texts[..texts.len() - 1], units, "{granularity:?}");
+    }
+}
+
+#[test]
+fn units_are_the_color_of_their_first_grapheme() {
+    let text = "trans rights\nare human rights\n";
+    let graphemes = runs(&colorize(text, Granularity::Grapheme));
+    let words = runs(&colorize(text, Granularity::Word));
+    let mut first = 0;
+    for (escape, word) in &words[..words.len() - 1] {
+        assert_eq!(escape, &graphemes[first].0, "{word:?}");
+        first += word.chars().count();
+    }
+}
+
+#[test]
+fn units_split_between_writes() {
+    let text = "trans rights\nare human\n\nrights\n";
+    for granularity in [Granularity::Word, Granularity::Line, Granularity::Paragraph] {
+        let whole = colorize(text, granularity);
+        for split in 1..text.len() {
+            let (a, b) = text.split_at(split);
+            assert_eq!(
+                write(&[a, b], granularity, EscapeMode::Strip),
+                whole,
+                "{granularity:?} {split}"
+            );
+        }
+    }
+}
+
+#[test]
+fn units_keep_their_color_after_a_reset() {
+    let whole = runs(&colorize("trans rights", Granularity::Word));
+    let output = write(
+        &["tra\x1b[0mns rig\x1b[1;0mhts"],
+        Granularity::Word,
+        EscapeMode::Passthrough,
+    );
+    let passed = runs(&output);
+    let escapes: Vec<&str> = passed.iter().map(|(escape, _)| escape.as_str()).collect();
+    let texts: Vec<&str> = passed.iter().map(|(_, text)| text.as_str()).collect();
+    assert_eq!(texts, ["tra", "", "ns ", "rig", "", "hts", ""]);
+    let (trans, rights) = (whole[0].0.as_str(), whole[1].0.as_str());
+    assert_eq!(
+        escapes,
+        [trans, "[0m", trans, rights, "[1;0m", rights, "[0m"]
+    );
+
+    // flushing resets the colors too
+    let mut writer = ColorWriter::new(Bits24::new(FREQ), Vec::new(), transgender())
+        .with_granularity(Granularity::Word);
+    writer.write_all(b"tra").unwrap();
+    writer.flush().unwrap();
+    writer.write_all(b"ns rights").unwrap();
+    let flushed = runs(&writer.into_inner().unwrap());
+    assert_eq!(flushed[0].0, trans);
+    assert_eq!(flushed[2], (trans.to_owned(), "ns ".to_owned()));
+}
@@ -0,0 +1,138 @@
+//! `ParQueerCat` writes exactly the same bytes as `QueerCat`, wherever the input is split between threads
+#![cfg(feature = "rayon")]
+
+use queercat_lib::*;
+
+/// Lines with escapes, tabs, carriage returns, blank lines, leading whitespace and graphemes of many bytes, which
+/// end up at the edges of the chunks with some chunk size
+fn input() -> String {
+    let lines = [
+        "trans rights are human rights",
+        "\ttabs\tand\x1b[1mbold\x1b[0m and \x1b]8;;https://example.com\x07links\x1b]8;;\x07",
+        "",
+        "  leading spaces and e\u{301}\u{302} combining marks",
+        "progress 10%\rprogress 100%\r\n",
+        "👩‍👩‍👧 🏳️‍⚧️ families and flags",
+        "\x1b[38;5;12mset colors\x1b[39m and \x1b[0;1mresets",
+        "",
+        "",
+        "ünïcödé wörds ändërswhërë",
+        "\x1b[2K\x1b[1A cursor movement",
+    ];
+    let mut input = String::new();
+    for i in 0..40 {
+        input.push_str(lines[i % lines.len()]);
+        input.push('\n');
+    }
+    input
+}
+
+fn sequential<C: Colorizer>(
+    colorizer: C,
+    options: (EscapeMode, Granularity),
+    input: &[u8],
+) -> Vec<u8> {
+    let mut cat = QueerCat::new(colorizer, Vec::new(), transgender())
+        .with_escape_mode(options.0)
+        .with_granularity(options.1);
+    cat.cat(input).unwrap();
+    cat.into_inner()
+}
+
+fn parallel<C>(
+    colorizer: C,
+    options: (EscapeMode, Granularity),
+    chunk_size: usize,
+    input: &[u8],
+) -> Vec<u8>
+where
+    C: Colorizer + Clone + Send,
+    C::Color: Send,
+{
+    let mut cat = ParQueerCat::new(colorizer, Vec::new(), transgender())
+        .with_escape_mode(options.0)
+        .with_granularity(options.1)
+        .with_chunk_size(chunk_size);
+    cat.cat(input).unwrap();
+    cat.into_inner()
+}
+
+/// Compares the output of both for every chunk size up to `max_chunk_size`
+fn assert_same<C>(colorizer: &C, input: &[u8], max_chunk_size: usize)
+where
+    C: Colorizer + Clone + Send,
+    C::Color: Send,
+{
+    for escape_mode in [EscapeMode::Strip, EscapeMode::Passthrough] {
+        for granularity in [
+            Granularity::Grapheme,
+            Granularity::Word,
+            Granularity::Line,
+            Granularity::Paragraph,
+        ] {
+            let options = (escape_mode, granularity);
+            let expected = sequential(colorizer.clone(), options, input);
+            for chunk_size in 1..=max_chunk_size {
+                let output = parallel(colorizer.clone(), options, chunk_size, input);
+                assert!(output == expected, "{options:?} {chunk_size}");
+            }
+        }
+    }
+}
+
+fn threads(threads: usize, f: impl FnOnce() + Send) {
+    let pool = rayon::ThreadPoolBuilder::new()
+        .num_threads(threads)
+        .build()
+        .unwrap();
+    pool.install(f);
+}
+
+#[test]
+fn same_output_as_queercat() {
+    let input = input();
+    let flag = transgender();
+    threads(4, || {
+        assert_same(
+            &Bits24::new(QueerCatFrequency::Original),
+            input.as_bytes(),
+            80,
+        );
+        // neighboring units often have the same ansi color
+        assert_same(
+            &Ansi::new(&flag, QueerCatFrequency::Fast),
+            input.as_bytes(),
+            80,
+        );
+        assert_same(
+            &Basic::new(&flag, QueerCatFrequency::Original),
+            input.as_bytes(),
+            20,
+        );
+    });
+}
+
+#[test]
+fn same_output_on_one_thread() {
+    let input = input();
+    threads(1, || {
+        assert_same(
+            &Bits24::new(QueerCatFrequency::Original),
+            input.as_bytes(),
+            4,
+        );
+    });
+}
+
+#[test]
+fn lines_longer_than_a_block() {
+    let mut input = "a".repeat(10_000);
+    input.push_str("\x1b[1m");
+    input.push_str(&"b".repeat(10_000));
+    input.push_str("\nshort\nlines\n\x1b]0;");
+    input.push_str(&"c".repeat(10_000));
+    input.push_str("\x07\nmore\n");
+    threads(2, || {
+        assert_same(&Bits24::new(QueerCatFrequency::Fast), input.as_bytes(), 3);
+    });
+}
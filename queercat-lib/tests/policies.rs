@@ -0,0 +1,60 @@
+//! What every policy for escapes and invalid utf-8 does with the same input
+
+use queercat_lib::color::Color;
+use queercat_lib::*;
+use std::io;
+
+/// A flag with a single color, so the colors only have to be written again after resets
+const RED: Flag<'static> = Flag {
+    name: "red",
+    ansi_colors: &[196],
+    basic_colors: &[],
+    stripe_colors: &[Color::from_hex(0xff0000)],
+    factor: Extended::lit("4.0"),
+    color_method: ColorMethod::Stripes,
+};
+
+const COLOR: &str = "\x1b[38;2;255;0;0m";
+
+fn cat(input: &[u8], escape_mode: EscapeMode, invalid_utf8: InvalidUtf8) -> io::Result<Vec<u8>> {
+    let mut cat = QueerCat::new(Bits24::new(QueerCatFrequency::Original), Vec::new(), RED)
+        .with_escape_mode(escape_mode)
+        .with_invalid_utf8(invalid_utf8);
+    cat.cat(input)?;
+    Ok(cat.into_inner())
+}
+
+#[test]
+fn escape_modes() -> io::Result<()> {
+    let input = b"a\x1b[1;31mb\x1b[38;5;12mc\x1b[0md\x1b]0;title\x07e\x1b[2Kf\n";
+    let strip = cat(input, EscapeMode::Strip, InvalidUtf8::Replace)?;
+    assert_eq!(
+        String::from_utf8_lossy(&strip),
+        format!("{COLOR}abcdef\n\x1b[0m")
+    );
+    // only the foreground colors are filtered out, and the color is written again after the reset
+    let passthrough = cat(input, EscapeMode::Passthrough, InvalidUtf8::Replace)?;
+    assert_eq!(
+        String::from_utf8_lossy(&passthrough),
+        format!("{COLOR}a\x1b[1mbc\x1b[0m{COLOR}d\x1b]0;title\x07e\x1b[2Kf\n\x1b[0m")
+    );
+    Ok(())
+}
+
+#[test]
+fn invalid_utf8_policies() -> io::Result<()> {
+    let input = b"a\xffb\xc3";
+    let replace = cat(input, EscapeMode::Strip, InvalidUtf8::Replace)?;
+    assert_eq!(
+        String::from_utf8_lossy(&replace),
+        format!("{COLOR}a\u{fffd}b\u{fffd}\x1b[0m")
+    );
+    let raw = cat(input, EscapeMode::Strip, InvalidUtf8::Raw)?;
+    assert_eq!(raw, [COLOR.as_bytes(), b"a\xffb\xc3\x1b[0m"].concat());
+    let error = cat(input, EscapeMode::Strip, InvalidUtf8::Error).unwrap_err();
+    assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+    let error = error.into_inner().unwrap();
+    let error = error.downcast_ref::<InvalidUtf8Error>().unwrap();
+    assert_eq!(error.bytes(), b"\xff");
+    Ok(())
+}
@@ -0,0 +1,82 @@
+//! Graphemes that are split between reads or writes at any byte get one color, like when they arrive whole
+
+use queercat_lib::*;
+use std::io::{self, BufRead, Read, Write};
+
+/// Changes the color of every grapheme
+const FREQ: QueerCatFrequency = QueerCatFrequency::Custom(0.3, 0.07);
+
+/// Zero width joiner sequences, combining characters and `\r\n`, also right at the end
+const TEXTS: [&str; 4] = ["a👩‍👩‍👧e\u{301}🏳️‍⚧️x\r\ny", "trans 🏳️‍⚧️", "ne\u{301}\u{302}", "🧑🏽‍🤝‍🧑🏿"];
+
+fn colors(output: &[u8]) -> usize {
+    output.windows(7).filter(|w| w == b"\x1b[38;2;").count()
+}
+
+/// A reader that returns one chunk per read
+struct Chunks<'a>(Vec<&'a [u8]>);
+
+impl Read for Chunks<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let chunk = self.fill_buf()?;
+        let len = chunk.len().min(buf.len());
+        buf[..len].copy_from_slice(&chunk[..len]);
+        self.consume(len);
+        Ok(len)
+    }
+}
+
+impl BufRead for Chunks<'_> {
+    fn fill_buf(&mut self) -> io::Result<&[u8]> {
+        Ok(self.0.first().copied().unwrap_or_default())
+    }
+    fn consume(&mut self, amt: usize) {
+        if let Some(chunk) = self.0.first_mut() {
+            *chunk = &chunk[amt..];
+            if chunk.is_empty() {
+                self.0.remove(0);
+            }
+        }
+    }
+}
+
+fn cat(chunks: Vec<&[u8]>) -> io::Result<Vec<u8>> {
+    let mut output = Vec::new();
+    QueerCat::new(Bits24::new(FREQ), &mut output, transgender()).cat(Chunks(chunks))?;
+    Ok(output)
+}
+
+#[test]
+fn split_graphemes_get_one_color() -> io::Result<()> {
+    for text in TEXTS {
+        let graphemes = unicode_segmentation::UnicodeSegmentation::graphemes(text, true).count();
+        let whole = cat(vec![text.as_bytes()])?;
+        assert_eq!(colors(&whole), graphemes, "{text:?}");
+        for split in 1..text.len() {
+            let (a, b) = text.as_bytes().split_at(split);
+            assert_eq!(cat(vec![a, b])?, whole, "{text:?} {split}");
+
+            let mut writer = ColorWriter::new(Bits24::new(FREQ), Vec::new(), transgender());
+            writer.write_all(a)?;
+            writer.write_all(b)?;
+            assert_eq!(writer.into_inner()?, whole, "{text:?} {split}");
+
+            // flushing resets the colors, but the rest of the grapheme isn't colorized again
+            let mut writer = ColorWriter::new(Bits24::new(FREQ), Vec::new(), transgender());
+            writer.write_all(a)?;
+            writer.flush()?;
+            writer.write_all(b)?;
+            let flushed = writer.into_inner()?;
+            assert_eq!(colors(&flushed), graphemes, "{text:?} {split}");
+            let stripped: Vec<u8> = String::from_utf8_lossy(&flushed)
+                .split('\x1b')
+                .map(|run| run.split_once('m').map_or(run, |(_, text)| text))
+                .collect::<String>()
+                .into_bytes();
+            assert_eq!(stripped, text.as_bytes(), "{text:?} {split}");
+        }
+        let bytes = text.as_bytes().chunks(1).collect();
+        assert_eq!(cat(bytes)?, whole, "{text:?}");
+    }
+    Ok(())
+}
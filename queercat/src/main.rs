@@ -3,12 +3,20 @@
 
 #[allow(clippy::wildcard_imports)]
 // the functions are automatically generated and do not affect readability at all. clippy moment.
-use queercat_lib::{color::Color, flag::*, Ansi, Bits24, QueerCat, QueerCatFrequency};
+use queercat_lib::{
+    color::{AnsiColor, Color, Gradient, DEFAULT_GRADIENT_RESOLUTION},
+    flag::*,
+    Animation, Ansi, Basic, Bits24, Bits24Lut, Colorizer, ColumnMode, Cursor, EscapeMode, Geometry,
+    Granularity, InvalidUtf8, Moving, QueerCat, QueerCatFrequency, TextSize, DEFAULT_DURATION,
+    DEFAULT_SPEED, DEFAULT_TAB_WIDTH, RESTORE_TERMINAL,
+};
 
 use clap::{Args, Parser, ValueEnum};
 use is_terminal::IsTerminal;
+use std::collections::hash_map::RandomState;
 use std::fs::File;
-use std::io::{BufRead, BufReader, BufWriter, Result, Write};
+use std::hash::{BuildHasher, Hasher};
+use std::io::{BufRead, BufReader, BufWriter, Read, Result, Write};
 use std::path::PathBuf;
 
 /// Concatenate FILE(s), or standard input, to standard output.
@@ -17,6 +25,8 @@ use std::path::PathBuf;
 /// Report all queercat bugs to <https://github.com/4gboframram/queercat-rs/issues>
 #[derive(Parser)]
 #[command(author, version, about, long_about = None)]
+// commandline flags are bools
+#[allow(clippy::struct_excessive_bools)]
 struct Cli {
     #[command(flatten)]
     flag: FlagArg,
@@ -29,12 +39,75 @@ struct Cli {
     #[arg(required = false, short = 'b', long = "24bit")]
     bits24: bool,
 
-    /// Offset of the start of the flag
-    #[arg(short, long, default_value_t = 0.0)]
+    /// Only use the 16 basic colors, for terminals like the Linux console and serial consoles that don't support more
+    #[arg(long = "16color", conflicts_with = "bits24")]
+    basic: bool,
+
+    /// Dither the blends between colors in 256 and 16 color mode, so that they look smooth instead of turning into hard bands
+    #[arg(long, conflicts_with = "bits24")]
+    dither: bool,
+
+    /// Offset of the start of the flag, in flags. An offset of 0.5 starts halfway through the flag.
+    #[arg(short, long, default_value_t = 0.0, allow_negative_numbers = true, value_parser = finite)]
     offset: f32,
 
+    /// Start the flag at a pseudo-random offset that is picked by SEED, so that it looks the same every time
+    #[arg(short = 'S', long, conflicts_with = "random_offset")]
+    seed: Option<u64>,
+
+    /// Start the flag at a random offset that is different every time
+    #[arg(long)]
+    random_offset: bool,
+
+    /// Keep styles like bold and underline and other escape sequences from the input instead of removing them. Only text colors are replaced.
+    #[arg(short, long)]
+    passthrough: bool,
+
+    /// Count every character as one column instead of using its width in the terminal
+    #[arg(long)]
+    count_graphemes: bool,
+
+    /// The distance between tab stops
+    #[arg(long, default_value_t = DEFAULT_TAB_WIDTH)]
+    tab_width: u32,
+
+    /// What to do with input that is not valid UTF-8
+    #[arg(long, value_enum, default_value_t = InvalidUtf8Choice::Replace)]
+    invalid_utf8: InvalidUtf8Choice,
+
+    /// How much of the text gets the same color
+    #[arg(short, long, value_enum, default_value_t = GranularityChoice::Character)]
+    granularity: GranularityChoice,
+
     #[command(flatten)]
     frequency: Frequency,
+
+    /// Run the gradient the other way, from right to left and bottom to top
+    #[arg(short, long)]
+    reverse: bool,
+
+    #[command(flatten)]
+    geometry: GeometryArgs,
+
+    /// Blend the edges of the stripes of --horizontal-stripes and --vertical-stripes, using --factor for custom flags. Turns on --dither in 256 and 16 color mode
+    #[arg(long, requires = "stripe_geometry")]
+    blend: bool,
+
+    /// The number of lines in the input, so that --horizontal-stripes doesn't have to read all of it first
+    #[arg(long, requires = "horizontal_stripes")]
+    lines: Option<u32>,
+
+    /// Draw every line over and over with a moving gradient before going on to the next one, like lolcat -a. Only works when writing to a terminal.
+    #[arg(short = 'A', long)]
+    animate: bool,
+
+    /// The number of frames that every line is animated for
+    #[arg(long, default_value_t = DEFAULT_DURATION, requires = "animate")]
+    duration: u32,
+
+    /// The number of frames per second when animating
+    #[arg(long, default_value_t = DEFAULT_SPEED, requires = "animate", value_parser = finite)]
+    speed: f32,
 }
 
 #[derive(Args, Clone, PartialEq, PartialOrd)]
@@ -50,7 +123,7 @@ pub struct CustomFlag {
     ansi_codes: Vec<u8>,
 
     /// How long it takes to blend between stripe colors, with lower values taking longer to blend. Maxes out at just below 256.
-    #[arg(long)]
+    #[arg(long, value_parser = finite)]
     #[arg(default_value_t = 4.0)]
     factor: f32,
 }
@@ -70,7 +143,13 @@ pub struct FlagArg {
 #[derive(Clone, PartialEq, Eq, PartialOrd, ValueEnum)]
 pub enum FlagChoice {
     Rainbow,
-    #[value(alias("trans"), alias("tra"), alias("traaaa"), alias("traa"),  alias("traaa"))]
+    #[value(
+        alias("trans"),
+        alias("tra"),
+        alias("traaaa"),
+        alias("traa"),
+        alias("traaa")
+    )]
     Transgender,
     #[value(alias("enby"), alias("nb"))]
     NonBinary,
@@ -79,7 +158,7 @@ pub enum FlagChoice {
     Gay,
     #[value(alias("pan"))]
     Pansexual,
-    #[value(alias("bi"),  alias("bi-yourself"))]
+    #[value(alias("bi"), alias("bi-yourself"))]
     Bisexual,
 
     GenderFluid,
@@ -92,20 +171,100 @@ pub enum FlagChoice {
     Dutch,
 }
 
+impl FlagChoice {
+    /// The builtin flag that was chosen
+    fn to_flag(&self) -> Flag<'static> {
+        use FlagChoice::*;
+        match self {
+            Rainbow => rainbow(),
+            Transgender => transgender(),
+            NonBinary => nonbinary(),
+            Lesbian => lesbian(),
+            Gay => gay(),
+            Pansexual => pansexual(),
+            Bisexual => bisexual(),
+            GenderFluid => gender_fluid(),
+            Asexual => asexual(),
+            Unlabeled => unlabeled(),
+            Aromantic => aromantic(),
+            Aroace => aroace(),
+            Dutch => dutch(),
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, ValueEnum)]
+pub enum InvalidUtf8Choice {
+    /// Replace invalid bytes with the replacement character
+    Replace,
+    /// Write invalid bytes unchanged and without color
+    Raw,
+    /// Stop with an error
+    Error,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, ValueEnum)]
+pub enum GranularityChoice {
+    /// Every character gets its own color
+    #[value(alias("grapheme"))]
+    Character,
+    /// Every word gets one color
+    Word,
+    /// Every line gets one color
+    Line,
+    /// Every paragraph gets one color
+    Paragraph,
+}
+
 #[derive(Args, Clone, PartialEq, PartialOrd)]
 #[group(required = false)]
 struct Frequency {
-    /// Horizontal rainbow frequency
+    /// Horizontal rainbow frequency. A frequency of 1 goes through the flag every 15 columns, and negative frequencies go from right to left.
     #[arg(default_value_t = 0.1)]
-    #[arg(short = 'z', long)]
+    #[arg(short = 'z', long, allow_negative_numbers = true, value_parser = finite)]
     horizontal_frequency: f32,
 
-    /// Vertical rainbow frequency
+    /// Vertical rainbow frequency. A frequency of 1 goes through the flag every 30 lines, and negative frequencies go from bottom to top.
     #[arg(default_value_t = 0.23)]
-    #[arg(short, long)]
+    #[arg(short, long, allow_negative_numbers = true, value_parser = finite)]
     vertical_frequency: f32,
 }
 
+#[derive(Args, Clone, PartialEq, PartialOrd)]
+#[group(required = false, multiple = false)]
+#[allow(clippy::struct_excessive_bools)]
+struct GeometryArgs {
+    /// Draw straight stripes that move at an angle in degrees, clockwise from left to right
+    #[arg(long, allow_negative_numbers = true, value_parser = finite)]
+    angle: Option<f32>,
+
+    /// Draw rings around the center of the text. All of the input is read before anything is written.
+    #[arg(long)]
+    radial: bool,
+
+    /// Make every line go through the whole flag. All of the input is read before anything is written.
+    #[arg(long)]
+    per_line: bool,
+
+    /// Lay the flag out as horizontal stripes over the whole text, like the actual flag. All of the input is read before anything is written, unless --lines is given.
+    #[arg(long, group = "stripe_geometry")]
+    horizontal_stripes: bool,
+
+    /// Lay the flag out as vertical stripes over the width of the longest line. All of the input is read before anything is written.
+    #[arg(long, group = "stripe_geometry")]
+    vertical_stripes: bool,
+}
+
+/// Parses a number that isn't infinite or NaN
+fn finite(arg: &str) -> std::result::Result<f32, String> {
+    let value: f32 = arg.parse().map_err(|e| format!("{e}"))?;
+    if value.is_finite() {
+        Ok(value)
+    } else {
+        Err(format!("{arg} is not a finite number"))
+    }
+}
+
 fn get_file(path: &PathBuf) -> Result<Box<dyn BufRead>> {
     if path == std::path::Path::new("-") {
         Ok(Box::new(std::io::stdin().lock()))
@@ -114,67 +273,176 @@ fn get_file(path: &PathBuf) -> Result<Box<dyn BufRead>> {
     }
 }
 
-fn make_24bit<W: Write>(
-    writer: W,
-    flag: Flag<'_>,
+/// A seed that is different every time
+fn random_seed() -> u64 {
+    // std already gets randomness from the OS for hash maps
+    RandomState::new().build_hasher().finish()
+}
+
+/// Reads all of `files` one after another, or stdin if there are none
+fn open_files(files: &[PathBuf]) -> Result<Box<dyn BufRead>> {
+    use multi_reader::MultiReader;
+    if files.is_empty() {
+        return Ok(Box::new(std::io::stdin().lock()));
+    }
+    let mut readers = Vec::with_capacity(files.len());
+    // we can't use ? in iter.map()
+    for file in files {
+        let file = get_file(file)?;
+        readers.push(file);
+    }
+    Ok(Box::new(BufReader::new(MultiReader::new(
+        readers.into_iter(),
+    ))))
+}
+
+/// The geometry that `args` asks for. Geometries that depend on the size of the text read all of `reader` first,
+/// and replace it with what was read.
+fn geometry(
+    args: &GeometryArgs,
+    blend: bool,
+    lines: Option<u32>,
+    reader: &mut Box<dyn BufRead>,
+    cursor: Cursor,
+) -> Result<Geometry> {
+    if let Some(angle) = args.angle {
+        return Ok(Geometry::Angle(angle));
+    }
+    if let (true, Some(lines)) = (args.horizontal_stripes, lines) {
+        return Ok(Geometry::Stripes { lines, blend });
+    }
+    if !(args.radial || args.per_line || args.horizontal_stripes || args.vertical_stripes) {
+        return Ok(Geometry::Linear);
+    }
+
+    let mut input = Vec::new();
+    reader.read_to_end(&mut input)?;
+    let size = TextSize::measure(&input, cursor);
+    *reader = Box::new(std::io::Cursor::new(input));
+    Ok(if args.radial {
+        Geometry::radial(&size)
+    } else if args.per_line {
+        Geometry::per_line(size)
+    } else if args.horizontal_stripes {
+        Geometry::Stripes {
+            lines: size.lines(),
+            blend,
+        }
+    } else {
+        Geometry::VerticalStripes {
+            width: size.width(),
+            blend,
+        }
+    })
+}
+
+/// Settings that are shared between all colorizers
+struct Options {
     freq: QueerCatFrequency,
     offset: f32,
-) -> QueerCat<'_, W, Bits24> {
-    let c = Bits24::new(freq).with_offset(offset);
-    QueerCat::new(c, writer, flag)
+    seed: Option<u64>,
+    escape_mode: EscapeMode,
+    column_mode: ColumnMode,
+    tab_width: u32,
+    invalid_utf8: InvalidUtf8,
+    granularity: Granularity,
+    geometry: Geometry,
+    dither: bool,
 }
 
-fn make_ansi<W: Write>(
-    writer: W,
+/// Applies the settings that are shared between all colorizers to `colorizer`
+fn configure<C: Moving>(colorizer: C, options: &Options) -> C {
+    let colorizer = colorizer
+        .with_offset(options.offset)
+        .with_column_mode(options.column_mode)
+        .with_tab_width(options.tab_width)
+        .with_geometry(options.geometry.clone());
+    match options.seed {
+        Some(seed) => colorizer.with_seed(seed),
+        None => colorizer,
+    }
+}
+
+/// A 24 bit colorizer that only samples the gradient of the flag once
+fn bits24(flag: &Flag<'_>, options: &Options) -> Bits24Lut {
+    let c = configure(Bits24::new(options.freq), options);
+    let gradient = Gradient::from_fn(DEFAULT_GRADIENT_RESOLUTION, |theta| {
+        c.calculate_color(theta, flag)
+    });
+    Bits24Lut::new(c, gradient)
+}
+
+/// Colorizes `reader` with `colorizer`, or animates it with the gradient moved by an extra offset in every frame
+fn run<C: Colorizer + Moving + Clone>(
+    colorizer: C,
     flag: Flag<'_>,
-    freq: QueerCatFrequency,
-    offset: f32,
-) -> QueerCat<'_, W, Ansi> {
-    #[allow(clippy::cast_possible_truncation)]
-    let c = Ansi::new(flag.ansi_colors.len() as u32, freq).with_offset(offset);
-    QueerCat::new(c, writer, flag)
+    reader: Box<dyn BufRead>,
+    writer: Box<dyn Write>,
+    options: &Options,
+    animation: Option<(u32, f32)>,
+) -> Result<()> {
+    let Some((duration, speed)) = animation else {
+        return QueerCat::new(colorizer, writer, flag)
+            .with_escape_mode(options.escape_mode)
+            .with_invalid_utf8(options.invalid_utf8)
+            .with_granularity(options.granularity)
+            .cat(reader);
+    };
+    drop(writer);
+    let colorizers = move |offset| colorizer.clone().with_offset(offset);
+    animate(reader, flag, colorizers, options, duration, speed)
+}
+
+fn animate<C: Colorizer>(
+    input: Box<dyn BufRead>,
+    flag: Flag<'_>,
+    make_colorizer: impl Fn(f32) -> C,
+    options: &Options,
+    duration: u32,
+    speed: f32,
+) -> Result<()> {
+    // the animation writes to stdout without locking it, so that this can write to it between frames
+    ctrlc::set_handler(|| {
+        let mut stdout = std::io::stdout().lock();
+        let _ = stdout.write_all(RESTORE_TERMINAL);
+        let _ = stdout.flush();
+        // 128 + SIGINT, like shells do
+        std::process::exit(130);
+    })
+    .map_err(std::io::Error::other)?;
+    Animation::new(flag, make_colorizer)
+        .with_escape_mode(options.escape_mode)
+        .with_invalid_utf8(options.invalid_utf8)
+        .with_granularity(options.granularity)
+        .with_duration(duration)
+        .with_speed(speed)
+        .animate(input, std::io::stdout())
 }
 
 fn main() -> Result<()> {
     let cli = Cli::parse();
     let mut stripe_colors = Vec::new();
-    let mut ansi_colors = Vec::new();
-    let (bits24, flag) = if let Some(custom) = cli.flag.custom {
-        let ansi_colors_arr = custom.ansi_codes;
-        let stripes = custom.stripes;
-        stripe_colors.extend(stripes.iter().map(|x| Color::from_hex(*x)));
-        ansi_colors.extend(ansi_colors_arr);
-        let method = ColorMethod::Stripes;
-        (
-            ansi_colors.is_empty(),
-            Flag {
-                name: "",
-                ansi_colors: &ansi_colors,
-                stripe_colors: &stripe_colors,
-                color_method: method,
-                factor: queercat_lib::Extended::from_num(custom.factor),
-            },
-        )
+    let flag = if let Some(custom) = &cli.flag.custom {
+        stripe_colors.extend(custom.stripes.iter().map(|x| Color::from_hex(*x)));
+        // only one of them is given, so the stripes are made to look like the ansi codes. The ansi codes of stripes
+        // are picked by the colorizers.
+        stripe_colors.extend(
+            custom
+                .ansi_codes
+                .iter()
+                .map(|&code| AnsiColor(code).color()),
+        );
+        // without either, it's a rainbow with a custom factor
+        Flag {
+            name: "",
+            ansi_colors: &custom.ansi_codes,
+            basic_colors: &[],
+            stripe_colors: &stripe_colors,
+            color_method: ColorMethod::Stripes,
+            factor: queercat_lib::Extended::saturating_from_num(custom.factor),
+        }
     } else {
-        use FlagChoice::*;
-        (
-            cli.bits24,
-            match cli.flag.flag {
-                Rainbow => rainbow(),
-                Transgender => transgender(),
-                NonBinary => nonbinary(),
-                Lesbian => lesbian(),
-                Gay => gay(),
-                Pansexual => pansexual(),
-                Bisexual => bisexual(),
-                GenderFluid => gender_fluid(),
-                Asexual => asexual(),
-                Unlabeled => unlabeled(),
-                Aromantic => aromantic(),
-                Aroace => aroace(),
-                Dutch => dutch(),
-            },
-        )
+        cli.flag.flag.to_flag()
     };
 
     // If stdin is piped, then we probably want to observe it in real time, so we don't buffer it
@@ -186,28 +454,125 @@ fn main() -> Result<()> {
 
     let freq = cli.frequency;
     let freq = QueerCatFrequency::Custom(freq.vertical_frequency, freq.horizontal_frequency);
-    let offset = cli.offset;
-
-    if cli.files.is_empty() {
-        let stdin = std::io::stdin().lock();
-        if bits24 {
-            make_24bit(writer, flag, freq, offset).cat(stdin)
+    let freq = if cli.reverse { freq.reversed() } else { freq };
+    let mut options = Options {
+        freq,
+        offset: cli.offset,
+        seed: cli.seed.or_else(|| cli.random_offset.then(random_seed)),
+        escape_mode: if cli.passthrough {
+            EscapeMode::Passthrough
         } else {
-            make_ansi(writer, flag, freq, offset).cat(stdin)
-        }
-    } else {
-        use multi_reader::MultiReader;
-        let mut readers = Vec::with_capacity(cli.files.len());
-        // we can't use ? in iter.map()
-        for file in cli.files {
-            let file = get_file(&file)?;
-            readers.push(file);
-        }
-        let reader = BufReader::new(MultiReader::new(readers.drain(..)));
-        if bits24 {
-            make_24bit(writer, flag, freq, offset).cat(reader)
+            EscapeMode::Strip
+        },
+        column_mode: if cli.count_graphemes {
+            ColumnMode::Graphemes
         } else {
-            make_ansi(writer, flag, freq, offset).cat(reader)
+            ColumnMode::DisplayWidth
+        },
+        tab_width: cli.tab_width,
+        invalid_utf8: match cli.invalid_utf8 {
+            InvalidUtf8Choice::Replace => InvalidUtf8::Replace,
+            InvalidUtf8Choice::Raw => InvalidUtf8::Raw,
+            InvalidUtf8Choice::Error => InvalidUtf8::Error,
+        },
+        granularity: match cli.granularity {
+            GranularityChoice::Character => Granularity::Grapheme,
+            GranularityChoice::Word => Granularity::Word,
+            GranularityChoice::Line => Granularity::Line,
+            GranularityChoice::Paragraph => Granularity::Paragraph,
+        },
+        geometry: Geometry::Linear,
+        // the 256 and 16 color modes can only show blends by dithering them
+        dither: cli.dither || cli.blend,
+    };
+
+    let mut reader = open_files(&cli.files)?;
+    let cursor = Cursor::new()
+        .with_column_mode(options.column_mode)
+        .with_tab_width(options.tab_width);
+    options.geometry = geometry(&cli.geometry, cli.blend, cli.lines, &mut reader, cursor)?;
+
+    let animation =
+        (cli.animate && std::io::stdout().is_terminal()).then_some((cli.duration, cli.speed));
+    if cli.basic {
+        let basic = Basic::new(&flag, options.freq).with_dither(options.dither);
+        run(
+            configure(basic, &options),
+            flag,
+            reader,
+            writer,
+            &options,
+            animation,
+        )
+    } else if cli.bits24 {
+        let bits24 = bits24(&flag, &options);
+        run(bits24, flag, reader, writer, &options, animation)
+    } else {
+        let ansi = Ansi::new(&flag, options.freq).with_dither(options.dither);
+        run(
+            configure(ansi, &options),
+            flag,
+            reader,
+            writer,
+            &options,
+            animation,
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &[&str]) -> std::result::Result<Cli, clap::Error> {
+        Cli::try_parse_from(std::iter::once("queercat").chain(args.iter().copied()))
+    }
+
+    #[test]
+    fn numbers_have_to_be_finite() {
+        for arg in ["-z", "-v", "-o", "--angle", "--factor"] {
+            for value in ["nan", "inf", "-inf", "infinity"] {
+                assert!(parse(&[arg, value]).is_err(), "{arg} {value}");
+            }
         }
+        assert!(parse(&["-A", "--speed", "inf"]).is_err());
+    }
+
+    /// The output of colorizing 40 lines of text in horizontal stripes with `make_colorizer`
+    fn stripes<C: Colorizer + Moving>(
+        make_colorizer: impl Fn(&Flag<'_>) -> C,
+        blend: bool,
+    ) -> Vec<u8> {
+        let flag = transgender();
+        let colorizer = make_colorizer(&flag).with_geometry(Geometry::Stripes { lines: 40, blend });
+        let mut output = Vec::new();
+        QueerCat::new(colorizer, &mut output, flag)
+            .cat("trans rights are human rights\n".repeat(40).as_bytes())
+            .unwrap();
+        output
+    }
+
+    #[test]
+    fn blending_needs_dithering_with_fewer_colors() {
+        let ansi = |dither| {
+            move |flag: &Flag<'_>| Ansi::new(flag, QueerCatFrequency::Original).with_dither(dither)
+        };
+        let basic = |dither| {
+            move |flag: &Flag<'_>| Basic::new(flag, QueerCatFrequency::Original).with_dither(dither)
+        };
+        assert_eq!(stripes(ansi(false), true), stripes(ansi(false), false));
+        assert_ne!(stripes(ansi(true), true), stripes(ansi(true), false));
+        assert_eq!(stripes(basic(false), true), stripes(basic(false), false));
+        assert_ne!(stripes(basic(true), true), stripes(basic(true), false));
+    }
+
+    #[test]
+    fn large_numbers_are_accepted() {
+        let cli = parse(&["-z", "1e12", "-v", "-1e12", "-o", "1e30", "--factor", "1e9"]).unwrap();
+        assert_eq!(
+            cli.frequency.horizontal_frequency.to_bits(),
+            1e12f32.to_bits()
+        );
+        assert_eq!(cli.offset.to_bits(), 1e30f32.to_bits());
     }
 }